@@ -1,8 +1,9 @@
 use rmt::{http_context, http_gates};
 
 http_gates!(MyService [
+    /// Reverses the message and returns the previous one
     Msg {
-        request: { msg: String },
+        request: { msg: String [len(1, 256)] },
         response: { msg: String, last_msg: String }
     },
     Ping {
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 
-use rmt::{self, Origin, http::instance::Encryption, http_request, http_bind_worker, warn, rmtm};
+use rmt::{self, Origin, http::instance::Encryption, http_bind_worker, warn, rmtm};
 mod definitions;
 use definitions::*;
 
@@ -30,10 +30,8 @@ async fn process(self, _worker: &Self::W) -> Result<Self::Response, rmt::Error>
 
 #[rmtm::http_gate( MyService::Hello | ServiceWorker )]
 async fn process(self, worker: &Self::W) -> Result<Self::Response, rmt::Error> {
-    let new_msg = http_request! { 
-        SERVICE_CONTEXT | (worker.http_client.clone()) 
-        MyService : Msg { msg: self.msg }
-    }
+    let new_msg = MyServiceClient::new(&SERVICE_CONTEXT, worker.http_client.clone())
+        .msg(self.msg)
         .await
         .map(|res| res.msg + "1")
         .map_err(|e| warn!("{e}"))
@@ -65,6 +63,9 @@ async fn main() {
         http_client: reqwest::Client::new()
     };
 
+    // rmt.toml, RMT_* variables and --options override the settings below
+    let config = rmt::config::Config::load().expect("Invalid config");
+
     rmt::http::Instance::new(service_worker)
         .set_encryption(Encryption::None)
         .set_workers_count(2)
@@ -72,6 +73,9 @@ async fn main() {
         // .set_allowed_origins(vec![Origin::Remote { ip: "122.12.52.12", port: 0 }]);
         // Only local requests!
         .set_allowed_origins(vec![Origin::Local { port: 0 }])
+        .set_describe(rmt::http_build_info!())
+        .set_config(&config)
+        .expect("Config can't be applied")
         .run()
         .await
         .expect("Error in main func");
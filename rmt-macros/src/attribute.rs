@@ -52,9 +52,9 @@ impl Parse for HTTPGateArgs {
         let worker: syn::Type = input.parse()?;
 
         Ok(Self {
-            gate: gate,
-            service: service,
-            worker: worker
+            gate,
+            service,
+            worker
         })
     }
 }
\ No newline at end of file
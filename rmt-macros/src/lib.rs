@@ -1,18 +1,21 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{ItemFn, parse_macro_input};
+use syn::{ItemFn, ItemMod, parse_macro_input, parse_quote};
 
 mod attribute;
+mod service;
 use attribute::*;
 
 /** *Generates gate*
-    ```
+    ```ignore
     #[http_gate(MyService::GateName | Worker)]
     async fn process(self, worker: &Self::W) -> Result<Self::Response, Error> {
         // implementation
     }
     ```
-    Where `self` is the request
+    Where `self` is the request. Per-request data is taken as the third argument,
+    `context: &RequestContext`. When the handler doesn't need it, the macro adds
+    `_context: &rmt::http::RequestContext`, so the crate must be reachable as `rmt`
  */
 #[proc_macro_attribute]
 pub fn http_gate(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -22,7 +25,16 @@ pub fn http_gate(attr: TokenStream, item: TokenStream) -> TokenStream {
     let block = input.block;
     let attrs = input.attrs;
     let _vis = input.vis;
-    let sig = input.sig;
+    let mut sig = input.sig;
+
+    match sig.inputs.len() {
+        2 => sig.inputs.push(parse_quote!(_context: &rmt::http::RequestContext)),
+        3 => {},
+        _ => return syn::Error::new_spanned(
+            &sig.inputs,
+            "gate handler takes `self, worker: &Self::W` and optionally `context: &RequestContext`"
+        ).to_compile_error().into()
+    }
 
     let gate = args.gate;
     let service = args.service;
@@ -31,7 +43,6 @@ pub fn http_gate(attr: TokenStream, item: TokenStream) -> TokenStream {
     quote! {
         rmt::paste::paste! {
             impl rmt::http::Gate for [<RMTHTTP #service #gate Req>] {
-                type Response = [<RMTHTTP #service #gate Res>];
                 type W = #worker;
 
                 #(#attrs)*
@@ -45,6 +56,41 @@ pub fn http_gate(attr: TokenStream, item: TokenStream) -> TokenStream {
     }.into()
 }
 
+/** *Defines service*
+
+    Alternative to ```http_gates!```, generates the same types.
+    Gates are enums with ```Request``` and ```Response```, marked with ```#[gate]```.
+    Other items of the module are kept
+    ```ignore
+    /// Service name is the module name in PascalCase, unless set with `name = ...`
+    #[rmtm::service(version = "1.2.0", rename_all = "camelCase")]
+    pub mod my_service {
+        /// Reverses the message
+        #[gate(access = Internal, cache_ttl = Duration::from_secs(5))]
+        pub enum Msg {
+            Request { #[validate(len(1, 256))] msg: String },
+            Response { msg: String, last_msg: String }
+        }
+
+        #[gate]
+        pub enum Ping {
+            Request { },
+            Response { }
+        }
+    }
+    ```
+    Duplicate gates, reserved field and option names are reported on the gate tokens
+ */
+#[proc_macro_attribute]
+pub fn service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as service::ServiceArgs);
+    let module = parse_macro_input!(item as ItemMod);
+
+    service::expand(args, module)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
 #[proc_macro_attribute]
 pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as MainArgs);
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Attribute, Expr, Fields, Ident, Item, ItemEnum, ItemMod, Lit, LitStr, MetaNameValue, Token,
+    parse::{Parse, ParseStream}, punctuated::Punctuated, spanned::Spanned
+};
+
+// Tag of gate enums in payloads
+const RESERVED_FIELDS: &[&str] = &["gate"];
+// GateMeta fields filled from the gate and the service
+const RESERVED_OPTIONS: &[&str] = &["name", "attributes", "container", "request", "response"];
+// Methods of generated clients, gate methods are named in snake case
+const RESERVED_GATES: &[&str] = &["new", "context"];
+// Serde rename rules
+const RENAME_RULES: &[&str] = &[
+    "lowercase", "UPPERCASE", "PascalCase", "camelCase", "snake_case",
+    "SCREAMING_SNAKE_CASE", "kebab-case", "SCREAMING-KEBAB-CASE"
+];
+
+pub struct ServiceArgs {
+    pub name: Option<Ident>,
+    pub version: Option<LitStr>,
+    pub rename_all: Option<LitStr>
+}
+
+fn lit_str(expr: &Expr) -> syn::Result<LitStr> {
+    match expr {
+        Expr::Lit(syn::ExprLit { lit: Lit::Str(lit), .. }) => Ok(lit.clone()),
+        _ => Err(syn::Error::new(expr.span(), "expected string literal"))
+    }
+}
+
+impl Parse for ServiceArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self { name: None, version: None, rename_all: None };
+
+        for arg in Punctuated::<MetaNameValue, Token![,]>::parse_terminated(input)? {
+            let key = arg.path.get_ident()
+                .ok_or_else(|| syn::Error::new(arg.path.span(), "expected `name`, `version` or `rename_all`"))?;
+
+            match key.to_string().as_str() {
+                "name" => match &arg.value {
+                    Expr::Path(path) if path.path.get_ident().is_some() =>
+                        args.name = path.path.get_ident().cloned(),
+                    value => return Err(syn::Error::new(value.span(), "expected service name"))
+                },
+                "version" => {
+                    let version = lit_str(&arg.value)?;
+                    let major = version.value();
+                    if !major.trim_start_matches('v').starts_with(|char: char| char.is_ascii_digit()) {
+                        return Err(syn::Error::new(version.span(), "expected semantic version, e.g. \"1.2.0\""))
+                    }
+                    args.version = Some(version);
+                },
+                "rename_all" => {
+                    let rule = lit_str(&arg.value)?;
+                    if !RENAME_RULES.contains(&rule.value().as_str()) {
+                        return Err(syn::Error::new(rule.span(),
+                            format!("unknown rename rule, expected one of {}", RENAME_RULES.join(", "))))
+                    }
+                    args.rename_all = Some(rule);
+                },
+                _ => return Err(syn::Error::new(key.span(), "expected `name`, `version` or `rename_all`"))
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn pascal_case(ident: &Ident) -> Ident {
+    let name: String = ident.to_string()
+        .trim_start_matches("r#")
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    Ident::new(&name, ident.span())
+}
+
+// Same as `paste` snake case of client methods
+fn snake_case(ident: &Ident) -> String {
+    let mut name = String::new();
+
+    for (index, char) in ident.to_string().trim_start_matches("r#").chars().enumerate() {
+        if char.is_uppercase() && index > 0 && !name.ends_with('_') {
+            name.push('_');
+        }
+        name.extend(char.to_lowercase());
+    }
+    name
+}
+
+fn is_doc(attr: &Attribute) -> bool {
+    attr.path().is_ident("doc")
+}
+
+struct Gate {
+    name: Ident,
+    attrs: Vec<Attribute>,
+    options: Vec<MetaNameValue>,
+    request: Vec<TokenStream>,
+    response: Vec<TokenStream>
+}
+
+fn check_field_name(field: &Ident, errors: &mut Vec<syn::Error>) -> bool {
+    let reserved = RESERVED_FIELDS.contains(&field.to_string().trim_start_matches("r#"));
+    if reserved {
+        errors.push(syn::Error::new(field.span(),
+            format!("`{field}` is reserved for the gate tag of payloads")));
+    }
+
+    !reserved
+}
+
+// `field: Type [rules]` as accepted by http_gates!
+fn fields(fields: &Fields, request: bool, errors: &mut Vec<syn::Error>) -> Vec<TokenStream> {
+    let fields = match fields {
+        Fields::Named(fields) => &fields.named,
+        Fields::Unit => return Vec::new(),
+        Fields::Unnamed(fields) => {
+            errors.push(syn::Error::new(fields.span(), "payload fields must be named"));
+            return Vec::new()
+        }
+    };
+
+    fields.iter()
+        .filter_map(|field| {
+            let ident = field.ident.as_ref()
+                .filter(|ident| check_field_name(ident, errors))?;
+
+            let mut rules = Vec::new();
+            let mut attrs = Vec::new();
+
+            for attr in &field.attrs {
+                if !attr.path().is_ident("validate") {
+                    attrs.push(attr);
+                    continue
+                }
+
+                if !request {
+                    errors.push(syn::Error::new(attr.span(), "validation rules apply to request fields only"));
+                    continue
+                }
+
+                match attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+                    Ok(parsed) => rules.extend(parsed.into_iter().map(|rule| validation_rule(rule, errors))),
+                    Err(err) => errors.push(err)
+                }
+            }
+
+            let ty = &field.ty;
+            let rules = (!rules.is_empty()).then(|| quote! { [ #(#rules),* ] });
+
+            Some(quote! { #(#attrs)* #ident : #ty #rules })
+        })
+        .collect()
+}
+
+// Rule is a function of rmt::http::validation, e.g. `len(1, 64)` or `non_empty`
+fn validation_rule(rule: Expr, errors: &mut Vec<syn::Error>) -> TokenStream {
+    let valid = match &rule {
+        Expr::Path(path) => path.path.get_ident().is_some(),
+        Expr::Call(call) => matches!(&*call.func, Expr::Path(path) if path.path.get_ident().is_some()),
+        _ => false
+    };
+
+    if !valid {
+        errors.push(syn::Error::new(rule.span(), "expected validation rule, e.g. `len(1, 64)` or `non_empty`"));
+    }
+
+    match rule {
+        Expr::Call(call) => {
+            let func = call.func;
+            let args = call.args;
+            quote! { #func ( #args ) }
+        },
+        rule => quote! { #rule }
+    }
+}
+
+fn gate(item: &ItemEnum, errors: &mut Vec<syn::Error>) -> Gate {
+    let mut options = Vec::new();
+    let mut attrs = Vec::new();
+
+    for attr in &item.attrs {
+        if !attr.path().is_ident("gate") {
+            attrs.push(attr.clone());
+            continue
+        }
+
+        if matches!(attr.meta, syn::Meta::Path(_)) {
+            continue
+        }
+
+        match attr.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated) {
+            Ok(parsed) => options.extend(parsed),
+            Err(err) => errors.push(err)
+        }
+    }
+
+    options.retain(|option| match option.path.get_ident() {
+        Some(key) if RESERVED_OPTIONS.contains(&key.to_string().as_str()) => {
+            errors.push(syn::Error::new(key.span(), format!("`{key}` is generated from the gate")));
+            false
+        },
+        _ => true
+    });
+
+    if !item.generics.params.is_empty() {
+        errors.push(syn::Error::new(item.generics.span(), "gates can't be generic"));
+    }
+
+    let mut request = None;
+    let mut response = None;
+
+    for variant in &item.variants {
+        if let Some(attr) = variant.attrs.iter().find(|attr| !is_doc(attr)) {
+            errors.push(syn::Error::new(attr.span(), "payload attributes are set on the gate"));
+        }
+
+        let slot = match variant.ident.to_string().as_str() {
+            "Request" => &mut request,
+            "Response" => &mut response,
+            _ => {
+                errors.push(syn::Error::new(variant.ident.span(), "expected `Request` or `Response`"));
+                continue
+            }
+        };
+
+        if slot.is_some() {
+            errors.push(syn::Error::new(variant.ident.span(), format!("duplicate `{}`", variant.ident)));
+            continue
+        }
+
+        *slot = Some(fields(&variant.fields, variant.ident == "Request", errors));
+    }
+
+    for (name, payload) in [("Request", &request), ("Response", &response)] {
+        if payload.is_none() {
+            errors.push(syn::Error::new(item.ident.span(), format!("gate `{}` has no `{name}`", item.ident)));
+        }
+    }
+
+    Gate {
+        name: item.ident.clone(),
+        attrs,
+        options,
+        request: request.unwrap_or_default(),
+        response: response.unwrap_or_default()
+    }
+}
+
+pub fn expand(args: ServiceArgs, module: ItemMod) -> syn::Result<TokenStream> {
+    let Some((_, items)) = &module.content else {
+        return Err(syn::Error::new(module.span(), "service module must be inline, e.g. `mod my_service { ... }`"))
+    };
+
+    let mut errors = Vec::new();
+    let mut gates: Vec<Gate> = Vec::new();
+    let mut seen: HashMap<String, Ident> = HashMap::new();
+    let mut other = Vec::new();
+
+    for item in items {
+        let item = match item {
+            Item::Enum(item) if item.attrs.iter().any(|attr| attr.path().is_ident("gate")) => item,
+            item => {
+                other.push(item);
+                continue
+            }
+        };
+
+        let gate = gate(item, &mut errors);
+
+        if let Some(first) = seen.get(&gate.name.to_string()) {
+            let mut err = syn::Error::new(gate.name.span(), format!("duplicate gate `{}`", gate.name));
+            err.combine(syn::Error::new(first.span(), "first defined here"));
+            errors.push(err);
+            continue
+        }
+
+        seen.insert(gate.name.to_string(), gate.name.clone());
+
+        let method = snake_case(&gate.name);
+        if RESERVED_GATES.contains(&method.as_str()) {
+            errors.push(syn::Error::new(gate.name.span(),
+                format!("gate `{}` collides with `{method}` method of generated clients", gate.name)));
+            continue
+        }
+
+        gates.push(gate);
+    }
+
+    let service = args.name.unwrap_or_else(|| pascal_case(&module.ident));
+    let service_docs = module.attrs.iter().filter(|attr| is_doc(attr));
+    let version = args.version.map(|version| quote! { version #version });
+    let rename_all = args.rename_all.map(|rule| quote! { rename_all #rule });
+
+    let gates = gates.iter().map(|gate| {
+        let Gate { name, attrs, options, request, response } = gate;
+        let options = options.iter().map(|option| {
+            let (key, value) = (&option.path, &option.value);
+            quote! { , #key : #value }
+        });
+
+        quote! {
+            #(#attrs)*
+            #name {
+                request: { #(#request),* },
+                response: { #(#response),* }
+                #(#options)*
+            }
+        }
+    });
+
+    let attrs = &module.attrs;
+    let vis = &module.vis;
+    let ident = &module.ident;
+    let unsafety = &module.unsafety;
+
+    // Valid gates are still generated, so errors don't cascade to the users of the service
+    let errors = errors.into_iter()
+        .reduce(|mut all, err| { all.combine(err); all })
+        .map(|err| err.to_compile_error());
+
+    Ok(quote! {
+        #errors
+
+        #(#attrs)*
+        #vis #unsafety mod #ident {
+            #(#other)*
+
+            rmt::http_gates! {
+                #(#service_docs)*
+                #service #version #rename_all [ #(#gates),* ]
+            }
+        }
+    })
+}
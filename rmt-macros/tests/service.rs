@@ -0,0 +1,7 @@
+#[test]
+fn service() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/service.rs");
+    cases.compile_fail("tests/ui/reserved_*.rs");
+    cases.compile_fail("tests/ui/handler_*.rs");
+}
@@ -0,0 +1,18 @@
+#[rmt::rmtm::service]
+pub mod my_service {
+    #[gate]
+    pub enum Ping {
+        Request { },
+        Response { }
+    }
+}
+
+#[derive(Clone)]
+pub struct MyWorker;
+
+#[rmt::rmtm::http_gate(MyService::Ping | MyWorker)]
+async fn process(self) -> Result<Self::Response, rmt::Error> {
+    Ok(Self::Response { })
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use rmt::http_gates;
+
+http_gates!(MyService [
+    /// Checked before the reserved one
+    Ping {
+        request: { },
+        response: { },
+        access: Internal
+    },
+    Context {
+        request: { },
+        response: { }
+    }
+]);
+
+fn main() {}
@@ -0,0 +1,22 @@
+#[rmt::rmtm::service]
+pub mod my_service {
+    #[gate]
+    pub enum New {
+        Request { gate: String },
+        Response { }
+    }
+
+    #[gate(container = &[])]
+    pub enum Context {
+        Request { },
+        Response { }
+    }
+
+    #[gate]
+    pub enum Ping {
+        Request { },
+        Response { }
+    }
+}
+
+fn main() {}
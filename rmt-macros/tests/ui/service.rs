@@ -0,0 +1,54 @@
+use rmt::http::{GateDefinition, Service, schema::ServiceSchema};
+
+/// Messages of users
+#[rmt::rmtm::service(version = "1.2.0", rename_all = "camelCase")]
+pub mod my_service {
+    /// Reverses the message
+    #[gate(access = Internal, max_in_flight = 4)]
+    pub enum Msg {
+        Request { #[validate(len(1, 256))] msg: String, reply_to: Option<String> },
+        Response { msg: String, last_msg: String }
+    }
+
+    #[gate]
+    pub enum Ping {
+        Request { },
+        Response { }
+    }
+}
+use my_service::*;
+
+static CONTEXT: rmt::http::Context<MyService> = rmt::http_context![ ::2020 ];
+
+#[derive(Clone)]
+pub struct MyWorker;
+
+impl rmt::http::Worker for MyWorker {
+    rmt::http_bind_worker!{ CONTEXT | MyService }
+}
+
+// Handler with the request context
+#[rmt::rmtm::http_gate(MyService::Msg | MyWorker)]
+async fn process(self, _worker: &Self::W, context: &rmt::http::RequestContext) -> Result<Self::Response, rmt::Error> {
+    Ok(Self::Response { msg: self.msg.chars().rev().collect(), last_msg: context.trace_id.clone() })
+}
+
+// Context argument is added by the macro
+#[rmt::rmtm::http_gate(MyService::Ping | MyWorker)]
+async fn process(self, _worker: &Self::W) -> Result<Self::Response, rmt::Error> {
+    Ok(Self::Response { })
+}
+
+fn main() {
+    assert_eq!(MyService::NAME, "MyService");
+    assert_eq!(MyService::VERSION, "1.2.0");
+    assert_eq!(<RMTHTTPMyServiceMsgReq as GateDefinition>::META.max_in_flight, 4);
+
+    let schema = ServiceSchema::of::<MyService>();
+    let msg = &schema.gates[0];
+    assert_eq!(msg.doc.as_deref(), Some("Reverses the message"));
+    assert_eq!(msg.request[1].name, "replyTo");
+
+    let errors = RMTHTTPMyServiceMsgReq { msg: String::new(), reply_to: None }.validate();
+    assert_eq!(errors[0].field, "msg");
+}
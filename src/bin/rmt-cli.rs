@@ -0,0 +1,372 @@
+use std::{io::Read, process::ExitCode, time::Duration};
+
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+use rmt::http::{context::route, idempotency::{IDEMPOTENCY_KEY_HEADER, idempotency_key}, version::VERSION_HEADER};
+
+const USAGE: &str = "\
+Usage: rmt-cli [options] <host:port> call <Gate> [json | -]
+       rmt-cli [options] <host:port> gates
+
+Request json is read from stdin when omitted or `-`.
+Route, mount and internal port are resolved from /describe when the service exposes it.
+/describe is served on the internal port, set with --internal-port when it is separate.
+
+Options:
+  --service <Name>            service of the gate, when several are hosted
+  --mount <path>              mount path of the service
+  --internal                  call the internal route
+  --internal-port <port>      port of internal gates
+  --service-version <version> version header of the request
+  --timeout <seconds>         default 5
+  --tls                       use https
+  --ca-cert <pem>             trust the certificate
+  --insecure                  accept invalid certificates";
+
+#[derive(Default)]
+struct Options {
+    service: Option<String>,
+    mount: Option<String>,
+    internal: bool,
+    internal_port: Option<u16>,
+    version: Option<String>,
+    timeout: Option<u64>,
+    tls: bool,
+    ca_cert: Option<String>,
+    insecure: bool
+}
+
+// Gate route resolved from the introspection of the service
+struct Target {
+    mount: String,
+    internal: bool,
+    port: Option<u16>
+}
+
+fn parse(args: &[String]) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next()
+            .cloned()
+            .ok_or_else(|| format!("{arg} requires a value"));
+
+        match arg.as_str() {
+            "--service" => options.service = Some(value()?),
+            "--mount" => options.mount = Some(value()?),
+            "--internal" => options.internal = true,
+            "--internal-port" => options.internal_port = Some(value()?.parse().map_err(|_| "invalid port")?),
+            "--service-version" => options.version = Some(value()?),
+            "--timeout" => options.timeout = Some(value()?.parse().map_err(|_| "invalid timeout")?),
+            "--tls" => options.tls = true,
+            "--ca-cert" => options.ca_cert = Some(value()?),
+            "--insecure" => options.insecure = true,
+            "-h" | "--help" => return Err(String::new()),
+            option if option.starts_with("--") => return Err(format!("unknown option {option}")),
+            _ => positional.push(arg.clone())
+        }
+    }
+
+    Ok((options, positional))
+}
+
+fn client(options: &Options) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(options.timeout.unwrap_or(5)))
+        .tls_danger_accept_invalid_certs(options.insecure);
+
+    if let Some(path) = &options.ca_cert {
+        let pem = std::fs::read(path)
+            .map_err(|err| format!("CA certificate {path} can't be read. {err}"))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .map_err(|err| format!("CA certificate {path} is invalid. {err}"))?;
+
+        builder = builder.add_root_certificate(cert);
+    }
+
+    builder.build().map_err(|err| err.to_string())
+}
+
+fn base(options: &Options, address: &str, port: Option<u16>) -> String {
+    let scheme = if options.tls { "https" } else { "http" };
+
+    match port {
+        Some(port) => {
+            let host = address.rsplit_once(':').map(|(host, _)| host).unwrap_or(address);
+            format!("{scheme}://{host}:{port}")
+        },
+        None => format!("{scheme}://{address}")
+    }
+}
+
+fn describe(client: &Client, options: &Options, address: &str) -> Option<Value> {
+    client.get(format!("{}/describe", base(options, address, options.internal_port)))
+        .send()
+        .ok()
+        .filter(|response| response.status().is_success())?
+        .json()
+        .ok()
+}
+
+fn services<'a>(description: &'a Value, options: &'a Options) -> impl Iterator<Item = &'a Value> {
+    description["services"].as_array()
+        .into_iter()
+        .flatten()
+        .filter(|service| options.service.as_deref()
+            .is_none_or(|name| service["name"] == name))
+}
+
+fn resolve(description: Option<&Value>, options: &Options, gate: &str) -> Target {
+    let found: Vec<(&Value, &Value)> = description.into_iter()
+        .flat_map(|description| services(description, options))
+        .flat_map(|service| service["gates"].as_array()
+            .into_iter()
+            .flatten()
+            .filter(|candidate| candidate["name"] == gate)
+            .map(move |gate| (service, gate)))
+        .collect();
+
+    let (service, gate) = match found.as_slice() {
+        [found] => *found,
+        _ => return Target {
+            mount: options.mount.clone().unwrap_or_default(),
+            internal: options.internal,
+            port: options.internal.then_some(options.internal_port).flatten()
+        }
+    };
+
+    let internal = options.internal || gate["internal"].as_bool().unwrap_or_default();
+    let internal_port = options.internal_port
+        .or(service["internal_port"].as_u64().and_then(|port| u16::try_from(port).ok()));
+
+    Target {
+        mount: options.mount.clone()
+            .or(service["mount"].as_str().map(str::to_string))
+            .unwrap_or_default(),
+        internal,
+        port: internal.then_some(internal_port).flatten()
+    }
+}
+
+fn call(client: &Client, options: &Options, address: &str, gate: &str, input: Option<&String>) -> Result<(), String> {
+    let json = match input.map(String::as_str) {
+        Some("-") | None => {
+            let mut json = String::new();
+            std::io::stdin().read_to_string(&mut json)
+                .map_err(|err| format!("stdin can't be read. {err}"))?;
+            json
+        },
+        Some(json) => json.to_string()
+    };
+
+    let mut request: Value = match json.trim() {
+        "" => Value::Object(Default::default()),
+        json => serde_json::from_str(json).map_err(|err| format!("Invalid request json. {err}"))?
+    };
+
+    let Some(fields) = request.as_object_mut() else {
+        return Err("Request json must be an object".to_string())
+    };
+    fields.insert("gate".to_string(), Value::String(gate.to_string()));
+
+    let description = describe(client, options, address);
+    let target = resolve(description.as_ref(), options, gate);
+    let url = format!("{}{}", base(options, address, target.port), route(&target.mount, target.internal));
+
+    let mut builder = client.post(&url)
+        .json(&request)
+        .header(IDEMPOTENCY_KEY_HEADER, idempotency_key());
+
+    if let Some(version) = &options.version {
+        builder = builder.header(VERSION_HEADER, version);
+    }
+
+    let response = builder.send()
+        .map_err(|err| format!("Request to {url} failed. {err}"))?;
+
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    let json = serde_json::from_str::<Value>(&body).ok();
+
+    // Gate errors are returned with success status, without the gate tag
+    let failed = !status.is_success() 
+        || json.as_ref().is_some_and(|json| json.get("error").is_some() && json.get("gate").is_none());
+
+    let body = json
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        .unwrap_or(body);
+
+    if failed {
+        eprintln!("{status} {url}");
+        return Err(body)
+    }
+
+    println!("{body}");
+    Ok(())
+}
+
+fn fields(fields: &Value) -> String {
+    let fields: Vec<String> = fields.as_array()
+        .into_iter()
+        .flatten()
+        .map(|field| {
+            let optional = if field["required"] == false { "?" } else { "" };
+            format!("{}{}: {}", field["name"].as_str().unwrap_or_default(), optional, field["type"].as_str().unwrap_or_default())
+        })
+        .collect();
+
+    match fields.is_empty() {
+        true => "{ }".to_string(),
+        false => format!("{{ {} }}", fields.join(", "))
+    }
+}
+
+fn gates(client: &Client, options: &Options, address: &str) -> Result<(), String> {
+    let description = describe(client, options, address)
+        .ok_or("Service doesn't expose /describe")?;
+
+    for service in services(&description, options) {
+        let mount = service["mount"].as_str().unwrap_or_default();
+        println!("{} {} {}", service["name"].as_str().unwrap_or_default(), service["version"].as_str().unwrap_or_default(), mount);
+
+        for gate in service["gates"].as_array().into_iter().flatten() {
+            let access = if gate["internal"] == true { "internal" } else { "public" };
+            println!("  {} ({access})", gate["name"].as_str().unwrap_or_default());
+
+            if let Some(doc) = gate["doc"].as_str() {
+                println!("    {}", doc.replace('\n', "\n    "));
+            }
+
+            println!("    request:  {}", fields(&gate["request"]));
+            println!("    response: {}", fields(&gate["response"]));
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (options, positional) = match parse(&args) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("{err}");
+            }
+            eprintln!("{USAGE}");
+            return ExitCode::from(2)
+        }
+    };
+
+    let client = match client(&options) {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(2)
+        }
+    };
+
+    let result = match positional.as_slice() {
+        [address, command, gate, rest @ ..] if command == "call" && rest.len() <= 1 =>
+            call(&client, &options, address, gate, rest.first()),
+        [address, command] if command == "gates" => gates(&client, &options, address),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2)
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_and_positional_arguments_are_parsed() {
+        let (options, positional) = parse(&args(&[
+            "--service", "Users", "--mount", "/users", "--internal", "--internal-port", "2021",
+            "--service-version", "1.2.0", "--timeout", "9", "--tls", "--insecure",
+            "127.0.0.1:2020", "call", "Get", "{}"
+        ])).unwrap();
+
+        assert_eq!(options.service.as_deref(), Some("Users"));
+        assert_eq!(options.mount.as_deref(), Some("/users"));
+        assert!(options.internal && options.tls && options.insecure);
+        assert_eq!(options.internal_port, Some(2021));
+        assert_eq!(options.version.as_deref(), Some("1.2.0"));
+        assert_eq!(options.timeout, Some(9));
+        assert_eq!(positional, args(&["127.0.0.1:2020", "call", "Get", "{}"]));
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        assert_eq!(parse(&args(&["--internal-port", "port"])).err().as_deref(), Some("invalid port"));
+        assert_eq!(parse(&args(&["--timeout"])).err().as_deref(), Some("--timeout requires a value"));
+        assert_eq!(parse(&args(&["--verbose"])).err().as_deref(), Some("unknown option --verbose"));
+        assert_eq!(parse(&args(&["--help"])).err().as_deref(), Some(""));
+    }
+
+    #[test]
+    fn base_replaces_port() {
+        let options = Options { tls: true, ..Options::default() };
+
+        assert_eq!(base(&options, "127.0.0.1:2020", None), "https://127.0.0.1:2020");
+        assert_eq!(base(&Options::default(), "127.0.0.1:2020", Some(2021)), "http://127.0.0.1:2021");
+        assert_eq!(base(&Options::default(), "localhost", Some(2021)), "http://localhost:2021");
+    }
+
+    #[test]
+    fn gate_route_is_resolved_from_description() {
+        let description = json!({ "services": [
+            { "name": "Users", "mount": "/users", "internal_port": 2021, "gates": [
+                { "name": "Get", "internal": false },
+                { "name": "Purge", "internal": true }
+            ] },
+            { "name": "Orders", "mount": "/orders", "gates": [{ "name": "Get", "internal": false }] }
+        ] });
+
+        let target = resolve(Some(&description), &Options::default(), "Purge");
+        assert_eq!((target.mount.as_str(), target.internal, target.port), ("/users", true, Some(2021)));
+
+        // Gate of several services needs the service option
+        let target = resolve(Some(&description), &Options::default(), "Get");
+        assert_eq!((target.mount.as_str(), target.internal, target.port), ("", false, None));
+
+        let options = Options { service: Some("Orders".to_string()), ..Options::default() };
+        let target = resolve(Some(&description), &options, "Get");
+        assert_eq!((target.mount.as_str(), target.internal, target.port), ("/orders", false, None));
+
+        let options = Options { mount: Some("/v2".to_string()), internal: true, internal_port: Some(3000), ..Options::default() };
+        let target = resolve(None, &options, "Get");
+        assert_eq!((target.mount.as_str(), target.internal, target.port), ("/v2", true, Some(3000)));
+    }
+
+    #[test]
+    fn fields_are_listed_with_optional_marks() {
+        let gate = json!([
+            { "name": "id", "type": "u64", "required": true },
+            { "name": "limit", "type": "Option<u32>", "required": false }
+        ]);
+
+        assert_eq!(fields(&gate), "{ id: u64, limit?: Option<u32> }");
+        assert_eq!(fields(&json!([])), "{ }");
+    }
+}
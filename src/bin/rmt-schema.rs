@@ -0,0 +1,78 @@
+use std::{process::ExitCode, time::Duration};
+
+use rmt::http::{describe::{DESCRIBE_ROUTE, Description}, schema::{self, ServiceSchema}};
+
+const USAGE: &str = "\
+Usage: rmt-schema diff <old.json> <new.json>
+       rmt-schema dump <host:port> <Service> [out.json]
+
+diff exits with 1 on breaking changes.
+dump reads the schema of a running service from /describe, served on its internal port.";
+
+fn diff(old: &str, new: &str) -> ExitCode {
+    let (old, new) = match (ServiceSchema::read(old), ServiceSchema::read(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Schema can't be read. {err}");
+            return ExitCode::from(2)
+        }
+    };
+
+    let changes = schema::diff(&old, &new);
+    for change in &changes {
+        println!("{change}");
+    }
+
+    let breaking = changes.iter().filter(|change| change.breaking).count();
+    if breaking > 0 {
+        eprintln!("{breaking} breaking change(s) from {} {} to {}", old.name, old.version, new.version);
+        return ExitCode::FAILURE
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn dump(address: &str, service: &str, output: Option<&String>) -> Result<(), String> {
+    let url = format!("http://{address}{DESCRIBE_ROUTE}");
+
+    let description: Description = reqwest::blocking::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .map_err(|err| format!("Description can't be read from {url}. {err}"))?;
+
+    let schema = description.services.iter()
+        .find(|described| described.name == service)
+        .map(|described| described.schema())
+        .ok_or_else(|| format!("Service {service} is not described by {address}"))?;
+
+    match output {
+        Some(path) => schema.write(path).map_err(|err| format!("Schema can't be written to {path}. {err}")),
+        None => {
+            println!("{}", schema.to_json());
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, old, new] if command == "diff" => diff(old, new),
+        [command, address, service, output @ ..] if command == "dump" && output.len() <= 1 =>
+            match dump(address, service, output.first()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("{err}");
+                    ExitCode::FAILURE
+                }
+            },
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
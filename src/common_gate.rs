@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::Error;
+use crate::{Error, error::ServiceError, http::validation::FieldError};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GateErrorResponse {
-    error: String
+    pub(crate) error: String,
+    // Failed fields of a validation error
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) fields: Vec<FieldError>
 }
 
 /** **Service Gates**
@@ -15,7 +18,12 @@ pub trait Payload: Serialize + DeserializeOwned + Clone { }
 
 impl From<Error> for GateErrorResponse {
     fn from(value: Error) -> Self {
-        Self { error: value.to_string() }
+        let fields = match &value {
+            Error::Service(ServiceError::Validation(fields)) => fields.clone(),
+            _ => Vec::new()
+        };
+
+        Self { error: value.to_string(), fields }
     }
 }
 
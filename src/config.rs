@@ -0,0 +1,422 @@
+use std::{collections::BTreeMap, fmt::Display, path::{Path, PathBuf}};
+use serde::Deserialize;
+
+use crate::logger::LogLevel;
+
+/** Config file used when no other is set */
+pub const DEFAULT_CONFIG_FILE: &str = "rmt.toml";
+const ENV_PREFIX: &str = "RMT_";
+const SECRET_ENV_PREFIX: &str = "RMT_SECRET_";
+const FILE_SUFFIX: &str = "_file";
+// Keys of env and command line, secrets aside
+const KEYS: &[&str] = &["profile", "port", "workers", "log_level", "allowed_origins", "tls_cert", "tls_key", "tls_ca"];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: PathBuf, err: std::io::Error },
+    Parse { path: PathBuf, err: String },
+    Invalid { key: String, value: String },
+    UnknownProfile(String)
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, err } => write!(f, "Config file {} can't be read. {}", path.display(), err),
+            Self::Parse { path, err } => write!(f, "Config file {} is invalid. {}", path.display(), err),
+            Self::Invalid { key, value } => write!(f, "Invalid value {:?} of config {}", value, key),
+            Self::UnknownProfile(profile) => write!(f, "Unknown config profile {}", profile)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError { }
+
+impl From<ConfigError> for std::io::Error {
+    fn from(err: ConfigError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string())
+    }
+}
+
+// One source of settings. Later layers override earlier ones
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    profile: Option<String>,
+    port: Option<u16>,
+    workers: Option<usize>,
+    log_level: Option<String>,
+    allowed_origins: Option<Vec<String>>,
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+    tls_ca: Option<PathBuf>,
+    // Values of keys with `_file` suffix are paths of secret files
+    secrets: BTreeMap<String, String>
+}
+
+#[derive(Default)]
+struct ConfigFile {
+    base: Layer,
+    profiles: BTreeMap<String, Layer>
+}
+
+impl Layer {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::Invalid { key: key.to_string(), value: value.to_string() };
+
+        match key {
+            "profile" => self.profile = Some(value.to_string()),
+            "port" => self.port = Some(value.parse().map_err(|_| invalid())?),
+            "workers" => self.workers = Some(value.parse().map_err(|_| invalid())?),
+            "log_level" => self.log_level = Some(value.to_string()),
+            "allowed_origins" => self.allowed_origins = Some(
+                value.split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            ),
+            "tls_cert" => self.tls_cert = Some(value.into()),
+            "tls_key" => self.tls_key = Some(value.into()),
+            "tls_ca" => self.tls_ca = Some(value.into()),
+            _ => return Err(invalid())
+        }
+
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Layer) {
+        macro_rules! merge {
+            ($($field:ident),*) => { $( if other.$field.is_some() { self.$field = other.$field; } )* };
+        }
+
+        merge!(profile, port, workers, log_level, allowed_origins, tls_cert, tls_key, tls_ca);
+
+        // Secret value overrides secret file of the same name and vice versa
+        for name in other.secrets.keys() {
+            let name = name.strip_suffix(FILE_SUFFIX).unwrap_or(name);
+            self.secrets.remove(name);
+            self.secrets.remove(&format!("{name}{FILE_SUFFIX}"));
+        }
+        self.secrets.extend(other.secrets);
+    }
+}
+
+/** Secret value, hidden from debug output */
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+/** **Instance Config**
+
+    Settings loaded from a TOML file, ```RMT_*``` environment variables and command line,
+    each overriding the previous one. File profiles override the base of the file
+    ```toml
+    port = 2020
+    log_level = "info"
+    profile = "dev"
+
+    [profiles.prod]
+    workers = 8
+    allowed_origins = ["10.0.0.4", "10.0.0.5"]
+    tls_cert = "/etc/rmt/cert.pem"
+    tls_key = "/etc/rmt/key.pem"
+
+    [profiles.prod.secrets]
+    db_password_file = "/run/secrets/db_password"
+    ```
+    Keys are ```RMT_WORKERS``` in environment and ```--workers``` in command line.
+    Secrets are ```RMT_SECRET_DB_PASSWORD``` or ```RMT_SECRET_DB_PASSWORD_FILE```
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub profile: Option<String>,
+    pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub log_level: Option<LogLevel>,
+    pub allowed_origins: Option<Vec<String>>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    // CA of client certificates
+    pub tls_ca: Option<PathBuf>,
+    secrets: BTreeMap<String, Secret>
+}
+
+fn read_file(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path)
+        .map_err(|err| ConfigError::Io { path: path.to_path_buf(), err })
+}
+
+// Command line options in `--key value` or `--key=value` form, other arguments are ignored
+fn args_layer(args: &[String]) -> Result<(Layer, Option<PathBuf>), ConfigError> {
+    let mut layer = Layer::default();
+    let mut config = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let Some(option) = arg.strip_prefix("--") else {
+            continue
+        };
+
+        let (name, inline) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, None)
+        };
+
+        let key = name.replace('-', "_");
+        if key != "config" && !KEYS.contains(&key.as_str()) {
+            continue
+        }
+
+        let Some(value) = inline.or_else(|| args.next().cloned()) else {
+            return Err(ConfigError::Invalid { key, value: String::new() })
+        };
+
+        match key.as_str() {
+            "config" => config = Some(PathBuf::from(value)),
+            key => layer.set(key, &value)?
+        }
+    }
+
+    Ok((layer, config))
+}
+
+fn env_layer(vars: &[(String, String)]) -> Result<(Layer, Option<PathBuf>), ConfigError> {
+    let mut layer = Layer::default();
+    let mut config = None;
+
+    for (name, value) in vars {
+        if let Some(secret) = name.strip_prefix(SECRET_ENV_PREFIX) {
+            layer.secrets.insert(secret.to_ascii_lowercase(), value.clone());
+            continue
+        }
+
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            "config" => config = Some(PathBuf::from(value)),
+            key if KEYS.contains(&key) => layer.set(key, value)?,
+            _ => ()
+        }
+    }
+
+    Ok((layer, config))
+}
+
+impl Config {
+    /** Loads config from process environment and arguments.
+        File is set by ```--config``` or ```RMT_CONFIG```, ```rmt.toml``` is used if it exists
+     */
+    pub fn load() -> Result<Self, ConfigError> {
+        let vars: Vec<(String, String)> = std::env::vars().collect();
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        Self::from_sources(&vars, &args)
+    }
+
+    /** Loads config from the given environment variables and command line arguments */
+    pub fn from_sources(vars: &[(String, String)], args: &[String]) -> Result<Self, ConfigError> {
+        let (env, env_file) = env_layer(vars)?;
+        let (cli, cli_file) = args_layer(args)?;
+
+        let file = match cli_file.or(env_file) {
+            Some(path) => Some(Self::read(&path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Some(Self::read(Path::new(DEFAULT_CONFIG_FILE))?),
+            None => None
+        };
+
+        let (mut settings, mut profiles) = file
+            .map(|file| (file.base, file.profiles))
+            .unwrap_or_default();
+
+        let profile = cli.profile.clone()
+            .or(env.profile.clone())
+            .or(settings.profile.clone());
+
+        if let Some(profile) = &profile {
+            let layer = profiles.remove(profile)
+                .ok_or_else(|| ConfigError::UnknownProfile(profile.clone()))?;
+            settings.merge(layer);
+        }
+
+        settings.merge(env);
+        settings.merge(cli);
+        settings.profile = profile;
+
+        Self::resolve(settings)
+    }
+
+    fn read(path: &Path) -> Result<ConfigFile, ConfigError> {
+        let parse_error = |err: toml::de::Error| ConfigError::Parse { path: path.to_path_buf(), err: err.message().to_string() };
+
+        let mut table: toml::Table = toml::from_str(&read_file(path)?)
+            .map_err(parse_error)?;
+
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into().map_err(parse_error)?,
+            None => BTreeMap::new()
+        };
+
+        Ok(ConfigFile {
+            base: table.try_into().map_err(parse_error)?,
+            profiles
+        })
+    }
+
+    fn resolve(settings: Layer) -> Result<Self, ConfigError> {
+        let log_level = settings.log_level
+            .map(|level| level.parse()
+                .map_err(|()| ConfigError::Invalid { key: "log_level".to_string(), value: level.clone() }))
+            .transpose()?;
+
+        let mut secrets = BTreeMap::new();
+        for (name, value) in settings.secrets {
+            match name.strip_suffix(FILE_SUFFIX) {
+                Some(name) => {
+                    let secret = read_file(Path::new(&value))?;
+                    secrets.insert(name.to_string(), Secret(secret.trim_end_matches(['\r', '\n']).to_string()));
+                },
+                None => { secrets.entry(name).or_insert(Secret(value)); }
+            }
+        }
+
+        Ok(Self {
+            profile: settings.profile,
+            port: settings.port,
+            workers: settings.workers,
+            log_level,
+            allowed_origins: settings.allowed_origins,
+            tls_cert: settings.tls_cert,
+            tls_key: settings.tls_key,
+            tls_ca: settings.tls_ca,
+            secrets
+        })
+    }
+
+    /** Secret by name, e.g. ```db_password``` */
+    pub fn secret(&self, name: &str) -> Option<&Secret> {
+        self.secrets.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rmt-config-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    const FILE: &str = r#"
+        port = 1000
+        workers = 1
+        log_level = "info"
+        profile = "dev"
+
+        [profiles.dev]
+        workers = 2
+        allowed_origins = ["10.0.0.1"]
+
+        [profiles.prod]
+        workers = 8
+    "#;
+
+    #[test]
+    fn cli_overrides_env_overrides_file() {
+        let path = file("precedence.toml", FILE);
+        let path = path.to_str().unwrap();
+
+        let config = Config::from_sources(&[], &args(&["--config", path])).unwrap();
+        assert_eq!((config.port, config.workers), (Some(1000), Some(2)));
+        assert_eq!(config.allowed_origins, Some(vec!["10.0.0.1".to_string()]));
+
+        let env = vars(&[("RMT_PORT", "2000"), ("RMT_WORKERS", "3"), ("RMT_CONFIG", path)]);
+        let config = Config::from_sources(&env, &[]).unwrap();
+        assert_eq!((config.port, config.workers), (Some(2000), Some(3)));
+
+        let config = Config::from_sources(&env, &args(&["serve", "--port=3000", "--allowed-origins", "a, b"])).unwrap();
+        assert_eq!((config.port, config.workers), (Some(3000), Some(3)));
+        assert_eq!(config.allowed_origins, Some(vec!["a".to_string(), "b".to_string()]));
+        assert!(matches!(config.log_level, Some(LogLevel::Info)));
+    }
+
+    #[test]
+    fn profile_is_selected_by_the_last_source() {
+        let path = file("profiles.toml", FILE);
+        let path = path.to_str().unwrap();
+        let env = vars(&[("RMT_PROFILE", "prod")]);
+
+        let config = Config::from_sources(&env, &args(&["--config", path])).unwrap();
+        assert_eq!((config.profile.as_deref(), config.workers), (Some("prod"), Some(8)));
+        assert_eq!(config.allowed_origins, None);
+
+        let config = Config::from_sources(&env, &args(&["--config", path, "--profile", "dev"])).unwrap();
+        assert_eq!(config.workers, Some(2));
+
+        let err = Config::from_sources(&[], &args(&["--config", path, "--profile", "test"])).unwrap_err();
+        assert!(matches!(err, ConfigError::UnknownProfile(profile) if profile == "test"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let err = Config::from_sources(&vars(&[("RMT_PORT", "http")]), &[]).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key, .. } if key == "port"));
+
+        assert!(Config::from_sources(&[], &args(&["--workers"])).is_err());
+        assert!(Config::from_sources(&[], &args(&["--log-level", "loud"])).is_err());
+
+        let path = file("unknown.toml", "ports = 1");
+        assert!(matches!(Config::from_sources(&[], &args(&["--config", path.to_str().unwrap()])), Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn secrets_are_read_from_files_and_env() {
+        let secret = file("db_password", "from file\n");
+        let path = file("secrets.toml", &format!(r#"
+            [secrets]
+            db_password_file = "{}"
+            api_key = "from config"
+        "#, secret.display()));
+        let path = path.to_str().unwrap();
+
+        let config = Config::from_sources(&[], &args(&["--config", path])).unwrap();
+        assert_eq!(config.secret("db_password").unwrap().expose(), "from file");
+        assert_eq!(config.secret("api_key").unwrap().expose(), "from config");
+        assert!(config.secret("missing").is_none());
+
+        let env = vars(&[("RMT_SECRET_DB_PASSWORD", "from env"), ("RMT_CONFIG", path)]);
+        let config = Config::from_sources(&env, &[]).unwrap();
+        assert_eq!(config.secret("db_password").unwrap().expose(), "from env");
+        assert!(!format!("{config:?}").contains("from env"));
+    }
+
+    #[test]
+    fn missing_secret_file_fails() {
+        let env = vars(&[("RMT_SECRET_TOKEN_FILE", "/nonexistent/rmt/token")]);
+
+        assert!(matches!(Config::from_sources(&env, &[]), Err(ConfigError::Io { .. })));
+    }
+}
@@ -1,5 +1,5 @@
-use std::fmt::Display;
-use crate::http;
+use std::{fmt::Display, time::Duration};
+use crate::http::{self, validation::FieldError};
 
 #[derive(Clone, Debug)]
 pub enum Error {
@@ -14,6 +14,8 @@ impl Display for Error {
         match self {
             Self::Http(err) => write!(f, "Http error. {:?}", err),
             Self::Websocket => write!(f, "Websocket error."),
+            Self::Service(ServiceError::Validation(fields)) => write!(f, "Validation error. {}", 
+                fields.iter().map(|field| field.to_string()).collect::<Vec<_>>().join(", ")),
             Self::Service(err) => write!(f, "Service error. {:?}", err),
             Self::Custom(err) => write!(f, "Custom Error. {}", err.clone().unwrap_or("empty".to_string()))
         }
@@ -25,5 +27,16 @@ pub enum ServiceError {
     ServiceRequestTimeout,
     JSONParseError(String),
     WrongGate,
-    NotImplemented
+    WrongRoute,
+    Unauthorized,
+    RateLimited { retry_after: Option<Duration> },
+    Overloaded,
+    CircuitOpen,
+    IncompatibleVersion { requested: String, served: String },
+    Validation(Vec<FieldError>),
+    // Error returned by the gate handler
+    GateFailed(String),
+    JobNotFound,
+    JobFailed(String),
+    JobCancelled
 }
\ No newline at end of file
@@ -0,0 +1,274 @@
+use std::{collections::hash_map::RandomState, hash::BuildHasher, sync::{OnceLock, atomic::{AtomicU32, AtomicUsize, Ordering}}, time::{Duration, Instant}};
+use log::warn;
+
+use crate::{Origin, metrics, http::{Circuit, CircuitBreaker, breaker::BreakerCall}};
+
+/** Replica selection strategy */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    RoundRobin,
+    Random,
+    LeastInFlight
+}
+
+struct Replica {
+    // Service and address, used in logs and metrics of the replica circuit
+    name: &'static str,
+    in_flight: AtomicU32,
+    breaker: CircuitBreaker
+}
+
+/** **Balancer**
+
+    Client side load balancing of Context calls across service replicas.
+    Every replica has its own circuit, opened after ```max_failures``` failed calls in a row.
+    Ejected replica gets no calls for the ```ejection``` interval,
+    then the next call probes it: success restores the replica, failure ejects it again.
+    Calls fail with ```ServiceError::CircuitOpen``` when all replicas are ejected.
+    Balancer is referenced by contexts, so it is declared as a static
+    ```ignore
+    const REPLICAS: &[Origin] = &[
+        Origin::IP { addr: "10.0.0.1", port: 2020 },
+        Origin::IP { addr: "10.0.0.2", port: 2020 }
+    ];
+    static BALANCER: Balancer = Balancer::new(REPLICAS, Strategy::RoundRobin);
+
+    http_context![ ::2020 ].balancer(&BALANCER)
+    ```
+ */
+pub struct Balancer {
+    origins: &'static [Origin],
+    strategy: Strategy,
+    max_failures: u32,
+    ejection: Duration,
+    next: AtomicUsize,
+    replicas: OnceLock<Vec<Replica>>
+}
+
+// Call to a chosen replica. Cancelled calls are not recorded
+pub(crate) struct ReplicaCall<'a> {
+    balancer: &'a Balancer,
+    service: &'static str,
+    index: usize,
+    breaker: Option<BreakerCall<'a>>
+}
+
+impl Balancer {
+    pub const fn new(origins: &'static [Origin], strategy: Strategy) -> Self {
+        Self {
+            origins,
+            strategy,
+            max_failures: 3,
+            ejection: Duration::from_secs(30),
+            next: AtomicUsize::new(0),
+            replicas: OnceLock::new()
+        }
+    }
+
+    /** Failures in a row before ejection and ejection interval. Default 3 and 30 seconds */
+    pub const fn set_ejection(mut self, max_failures: u32, ejection: Duration) -> Self {
+        self.max_failures = max_failures;
+        self.ejection = ejection;
+        self
+    }
+
+    pub fn origins(&self) -> &'static [Origin] {
+        self.origins
+    }
+
+    /** Circuit of the replica, ```Open``` while it is ejected */
+    pub fn circuit(&self, index: usize) -> Option<Circuit> {
+        self.replicas.get()?
+            .get(index)
+            .map(|replica| replica.breaker.circuit())
+    }
+
+    fn replicas(&self, service: &str) -> &[Replica] {
+        self.replicas.get_or_init(|| self.origins.iter()
+            .map(|origin| Replica {
+                // Created once per replica
+                name: Box::leak(format!("{service}@{}:{}", origin.host(), origin.port()).into_boxed_str()),
+                in_flight: AtomicU32::new(0),
+                breaker: CircuitBreaker::consecutive(self.max_failures.max(1), self.ejection)
+            })
+            .collect())
+    }
+
+    fn choose(&self, replicas: &[Replica], available: &[usize]) -> Option<usize> {
+        match self.strategy {
+            Strategy::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::Relaxed);
+                available.get(next % available.len().max(1)).copied()
+            },
+            Strategy::Random => {
+                let random = RandomState::new().hash_one(Instant::now()) as usize;
+                available.get(random % available.len().max(1)).copied()
+            },
+            Strategy::LeastInFlight => available.iter()
+                .min_by_key(|index| replicas[**index].in_flight.load(Ordering::Relaxed))
+                .copied()
+        }
+    }
+
+    /** Chooses a replica with a closed circuit, or one ready to be probed.
+        Returns ```None``` when all of them are ejected
+     */
+    pub(crate) fn acquire(&self, service: &'static str) -> Option<ReplicaCall<'_>> {
+        let replicas = self.replicas(service);
+
+        let mut available: Vec<usize> = (0..replicas.len())
+            .filter(|index| replicas[*index].breaker.is_available())
+            .collect();
+
+        // Probes taken by concurrent calls are skipped
+        while let Some(index) = self.choose(replicas, &available) {
+            let replica = &replicas[index];
+
+            if let Some(breaker) = replica.breaker.acquire(replica.name) {
+                replica.in_flight.fetch_add(1, Ordering::Relaxed);
+                return Some(ReplicaCall { balancer: self, service, index, breaker: Some(breaker) })
+            }
+
+            available.retain(|available| *available != index);
+        }
+
+        warn!("All replicas of {} service are ejected", service);
+        None
+    }
+
+    fn report_ejected(&self, service: &str) {
+        let ejected = self.replicas.get()
+            .map(|replicas| replicas.iter()
+                .filter(|replica| replica.breaker.circuit() != Circuit::Closed)
+                .count())
+            .unwrap_or_default();
+
+        metrics::set(&format!("rmt.balancer.{service}.ejected"), ejected as i64);
+    }
+}
+
+impl ReplicaCall<'_> {
+    pub(crate) fn origin(&self) -> &'static Origin {
+        &self.balancer.origins[self.index]
+    }
+
+    pub(crate) fn record(mut self, success: bool) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.record(success);
+        }
+
+        self.balancer.report_ejected(self.service);
+    }
+}
+
+impl Drop for ReplicaCall<'_> {
+    fn drop(&mut self) {
+        if let Some(replica) = self.balancer.replicas.get().and_then(|replicas| replicas.get(self.index)) {
+            replica.in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE: &str = "BalancerTest";
+    const REPLICAS: &[Origin] = &[
+        Origin::IP { addr: "10.0.0.1", port: 2020 },
+        Origin::IP { addr: "10.0.0.2", port: 2020 }
+    ];
+
+    fn call(balancer: &Balancer, success: bool) -> usize {
+        let call = balancer.acquire(SERVICE).expect("replica is available");
+        let index = call.index;
+        call.record(success);
+        index
+    }
+
+    // Fails calls until the replica is ejected, calls to others succeed
+    fn eject(balancer: &Balancer, ejected: usize) {
+        while balancer.circuit(ejected) != Some(Circuit::Open) {
+            let call = balancer.acquire(SERVICE).expect("replica is available");
+            let success = call.index != ejected;
+            call.record(success);
+        }
+    }
+
+    #[test]
+    fn round_robin_uses_every_replica() {
+        let balancer = Balancer::new(REPLICAS, Strategy::RoundRobin);
+
+        let mut used: Vec<usize> = (0..4).map(|_| call(&balancer, true)).collect();
+        used.sort();
+        assert_eq!(used, vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let balancer = Balancer::new(&REPLICAS[..1], Strategy::RoundRobin).set_ejection(2, Duration::from_secs(60));
+
+        for success in [false, true, false, true, false] {
+            call(&balancer, success);
+        }
+
+        assert_eq!(balancer.circuit(0), Some(Circuit::Closed));
+    }
+
+    #[test]
+    fn failing_replica_is_ejected() {
+        let balancer = Balancer::new(REPLICAS, Strategy::RoundRobin).set_ejection(2, Duration::from_secs(60));
+        eject(&balancer, 0);
+
+        for _ in 0..4 {
+            assert_eq!(call(&balancer, true), 1);
+        }
+        assert_eq!(balancer.circuit(1), Some(Circuit::Closed));
+    }
+
+    #[test]
+    fn all_ejected_replicas_fail_fast() {
+        let balancer = Balancer::new(&REPLICAS[..1], Strategy::RoundRobin).set_ejection(1, Duration::from_secs(60));
+        call(&balancer, false);
+
+        assert!(balancer.acquire(SERVICE).is_none());
+    }
+
+    #[test]
+    fn successful_probe_restores_replica() {
+        let balancer = Balancer::new(REPLICAS, Strategy::LeastInFlight).set_ejection(1, Duration::from_millis(20));
+        eject(&balancer, 0);
+        std::thread::sleep(Duration::from_millis(30));
+
+        // Probe is the only call let through to the ejected replica
+        let probe = balancer.acquire(SERVICE).unwrap();
+        assert_eq!(probe.index, 0);
+        assert_eq!(balancer.circuit(0), Some(Circuit::HalfOpen));
+        assert_eq!(call(&balancer, true), 1);
+
+        probe.record(true);
+        assert_eq!(balancer.circuit(0), Some(Circuit::Closed));
+    }
+
+    #[test]
+    fn failed_probe_ejects_again() {
+        let balancer = Balancer::new(&REPLICAS[..1], Strategy::RoundRobin).set_ejection(1, Duration::from_millis(20));
+        call(&balancer, false);
+        std::thread::sleep(Duration::from_millis(30));
+
+        call(&balancer, false);
+
+        assert_eq!(balancer.circuit(0), Some(Circuit::Open));
+        assert!(balancer.acquire(SERVICE).is_none());
+    }
+
+    #[test]
+    fn cancelled_call_is_not_recorded() {
+        let balancer = Balancer::new(&REPLICAS[..1], Strategy::RoundRobin).set_ejection(1, Duration::from_secs(60));
+
+        drop(balancer.acquire(SERVICE));
+
+        assert_eq!(balancer.circuit(0), Some(Circuit::Closed));
+        assert_eq!(balancer.replicas.get().unwrap()[0].in_flight.load(Ordering::Relaxed), 0);
+    }
+}
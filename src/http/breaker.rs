@@ -0,0 +1,296 @@
+use std::{sync::Mutex, time::{Duration, Instant}};
+use log::{info, warn};
+
+use crate::metrics;
+
+/** Circuit state. Reported as ```rmt.circuit.<Service>.state``` metric */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    Closed = 0,
+    Open = 1,
+    HalfOpen = 2
+}
+
+struct BreakerState {
+    circuit: Circuit,
+    window_start: Option<Instant>,
+    successes: u32,
+    failures: u32,
+    opened: Option<Instant>,
+    probes: u32
+}
+
+/** **Circuit Breaker**
+
+    Optional breaker of outgoing Context calls.
+    Opens when ```failure_rate``` of at least ```min_requests``` calls inside of ```window``` fail.
+    While open, calls fail immediately with ```ServiceError::CircuitOpen```.
+    After ```open_interval``` up to ```probes``` calls are let through,
+    first success closes the circuit and failure opens it again.
+    ```ignore
+    http_context![ ::2020 ].circuit_breaker(
+        CircuitBreaker::new(0.5, 10, Duration::from_secs(10), Duration::from_secs(5))
+    )
+    ```
+ */
+pub struct CircuitBreaker {
+    failure_rate: f64,
+    min_requests: u32,
+    window: Duration,
+    open_interval: Duration,
+    probes: u32,
+    // Success resets failures, as in circuits of balancer replicas
+    consecutive: bool,
+    state: Mutex<BreakerState>
+}
+
+// Call let through by the breaker. Cancelled calls are not recorded
+pub(crate) struct BreakerCall<'a> {
+    breaker: &'a CircuitBreaker,
+    service: &'static str,
+    probe: bool,
+    recorded: bool
+}
+
+impl CircuitBreaker {
+    pub const fn new(failure_rate: f64, min_requests: u32, window: Duration, open_interval: Duration) -> Self {
+        Self {
+            failure_rate,
+            min_requests,
+            window,
+            open_interval,
+            probes: 1,
+            consecutive: false,
+            state: Mutex::new(BreakerState {
+                circuit: Circuit::Closed,
+                window_start: None,
+                successes: 0,
+                failures: 0,
+                opened: None,
+                probes: 0
+            })
+        }
+    }
+
+    // Opens after failures in a row
+    pub(crate) const fn consecutive(max_failures: u32, open_interval: Duration) -> Self {
+        let mut breaker = Self::new(1.0, max_failures, Duration::MAX, open_interval);
+        breaker.consecutive = true;
+        breaker
+    }
+
+    /** Calls let through while half-open. Default 1 */
+    pub const fn set_probes(mut self, probes: u32) -> Self {
+        self.probes = probes;
+        self
+    }
+
+    pub fn circuit(&self) -> Circuit {
+        self.lock().circuit
+    }
+
+    // Whether a call would be let through, without taking a probe
+    pub(crate) fn is_available(&self) -> bool {
+        let state = self.lock();
+
+        match state.circuit {
+            Circuit::Closed => true,
+            Circuit::Open => state.opened.is_some_and(|opened| opened.elapsed() >= self.open_interval),
+            Circuit::HalfOpen => state.probes < self.probes
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn transition(&self, state: &mut BreakerState, service: &str, circuit: Circuit, now: Instant) {
+        match circuit {
+            Circuit::Open => warn!("Circuit of {} service is open", service),
+            Circuit::HalfOpen => info!("Circuit of {} service is half-open", service),
+            Circuit::Closed => info!("Circuit of {} service is closed", service)
+        }
+
+        state.circuit = circuit;
+        state.opened = (circuit == Circuit::Open).then_some(now);
+        state.window_start = Some(now);
+        state.successes = 0;
+        state.failures = 0;
+        state.probes = 0;
+
+        metrics::set(&format!("rmt.circuit.{service}.state"), circuit as i64);
+    }
+
+    pub(crate) fn acquire(&self, service: &'static str) -> Option<BreakerCall<'_>> {
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        if state.circuit == Circuit::Open
+            && state.opened.is_some_and(|opened| now.duration_since(opened) >= self.open_interval)
+        {
+            self.transition(&mut state, service, Circuit::HalfOpen, now);
+        }
+
+        let probe = match state.circuit {
+            Circuit::Closed => false,
+            Circuit::HalfOpen if state.probes < self.probes => {
+                state.probes += 1;
+                true
+            },
+            _ => {
+                metrics::increment(&format!("rmt.circuit.{service}.rejected"), 1);
+                return None
+            }
+        };
+
+        Some(BreakerCall { breaker: self, service, probe, recorded: false })
+    }
+
+    fn record(&self, service: &str, probe: bool, success: bool) {
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        if probe {
+            state.probes = state.probes.saturating_sub(1);
+        }
+
+        match (state.circuit, success) {
+            (Circuit::HalfOpen, true) if probe => self.transition(&mut state, service, Circuit::Closed, now),
+            (Circuit::HalfOpen, false) if probe => self.transition(&mut state, service, Circuit::Open, now),
+            (Circuit::Closed, _) => {
+                if state.window_start.is_none_or(|start| now.duration_since(start) >= self.window)
+                    || (self.consecutive && success)
+                {
+                    state.window_start = Some(now);
+                    state.successes = 0;
+                    state.failures = 0;
+                }
+
+                if success { state.successes += 1 } else { state.failures += 1 }
+
+                let total = state.successes + state.failures;
+                if total >= self.min_requests
+                    && state.failures as f64 >= self.failure_rate * total as f64
+                {
+                    self.transition(&mut state, service, Circuit::Open, now);
+                }
+            },
+            _ => ()
+        }
+    }
+}
+
+impl BreakerCall<'_> {
+    pub(crate) fn record(mut self, success: bool) {
+        self.recorded = true;
+        self.breaker.record(self.service, self.probe, success);
+    }
+}
+
+impl Drop for BreakerCall<'_> {
+    fn drop(&mut self) {
+        if !self.recorded && self.probe {
+            let mut state = self.breaker.lock();
+            state.probes = state.probes.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE: &str = "BreakerTest";
+
+    fn breaker(open_interval: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(0.5, 4, Duration::from_secs(60), open_interval)
+    }
+
+    fn call(breaker: &CircuitBreaker, success: bool) {
+        breaker.acquire(SERVICE).expect("call is let through").record(success);
+    }
+
+    #[test]
+    fn stays_closed_below_min_requests() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        for _ in 0..3 {
+            call(&breaker, false);
+        }
+
+        assert_eq!(breaker.circuit(), Circuit::Closed);
+    }
+
+    #[test]
+    fn stays_closed_below_failure_rate() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        for success in [true, true, false, true, true, false] {
+            call(&breaker, success);
+        }
+
+        assert_eq!(breaker.circuit(), Circuit::Closed);
+    }
+
+    #[test]
+    fn opens_on_failure_rate_and_rejects() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        // Own service name, metrics are shared by tests
+        for success in [true, false, true, false] {
+            breaker.acquire("BreakerOpenTest").unwrap().record(success);
+        }
+
+        assert_eq!(breaker.circuit(), Circuit::Open);
+        assert!(breaker.acquire("BreakerOpenTest").is_none());
+        assert_eq!(metrics::get("rmt.circuit.BreakerOpenTest.state"), Some(Circuit::Open as i64));
+        assert_eq!(metrics::get("rmt.circuit.BreakerOpenTest.rejected"), Some(1));
+    }
+
+    #[test]
+    fn half_open_probe_success_closes() {
+        let breaker = breaker(Duration::ZERO);
+        for _ in 0..4 {
+            call(&breaker, false);
+        }
+        assert_eq!(breaker.circuit(), Circuit::Open);
+
+        let probe = breaker.acquire(SERVICE).expect("probe after open interval");
+        assert_eq!(breaker.circuit(), Circuit::HalfOpen);
+
+        // Only one probe at a time
+        assert!(breaker.acquire(SERVICE).is_none());
+
+        probe.record(true);
+        assert_eq!(breaker.circuit(), Circuit::Closed);
+    }
+
+    #[test]
+    fn half_open_probe_failure_opens_again() {
+        let breaker = breaker(Duration::from_millis(20));
+        for _ in 0..4 {
+            call(&breaker, false);
+        }
+        assert!(breaker.acquire(SERVICE).is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+        call(&breaker, false);
+
+        assert_eq!(breaker.circuit(), Circuit::Open);
+        assert!(breaker.acquire(SERVICE).is_none());
+    }
+
+    #[test]
+    fn cancelled_probe_frees_its_slot() {
+        let breaker = breaker(Duration::ZERO).set_probes(1);
+        for _ in 0..4 {
+            call(&breaker, false);
+        }
+
+        drop(breaker.acquire(SERVICE).expect("probe"));
+
+        assert_eq!(breaker.circuit(), Circuit::HalfOpen);
+        assert!(breaker.acquire(SERVICE).is_some());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::BTreeMap, sync::Mutex, time::{Duration, Instant}};
+
+use crate::metrics;
+
+struct CacheEntry {
+    value: serde_json::Value,
+    expires: Instant,
+    inserted: u64
+}
+
+struct CacheState {
+    inserted: u64,
+    entries: BTreeMap<String, CacheEntry>
+}
+
+/** **Response Cache**
+
+    Bounded cache of Context responses for gates with ```cache_ttl``` option.
+    Entries are keyed on the gate name and the serialized request.
+    Hits and misses are reported as ```rmt.cache.<Service>.hit``` and ```rmt.cache.<Service>.miss``` metrics
+ */
+pub(crate) struct ResponseCache {
+    pub(crate) capacity: usize,
+    state: Mutex<CacheState>
+}
+
+// Gate name prefixes the request, so entries of a gate are a range
+pub(crate) fn cache_key(gate: &str, request: &str) -> String {
+    format!("{gate}\0{request}")
+}
+
+impl ResponseCache {
+    pub(crate) const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState { inserted: 0, entries: BTreeMap::new() })
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn get(&self, service: &str, key: &str) -> Option<serde_json::Value> {
+        let mut state = self.lock();
+
+        let value = match state.entries.get(key) {
+            Some(entry) if entry.expires > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                state.entries.remove(key);
+                None
+            },
+            None => None
+        };
+
+        let counter = if value.is_some() { "hit" } else { "miss" };
+        metrics::increment(&format!("rmt.cache.{service}.{counter}"), 1);
+
+        value
+    }
+
+    pub(crate) fn insert(&self, key: String, value: serde_json::Value, ttl: Duration) {
+        if self.capacity == 0 {
+            return
+        }
+
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            state.entries.retain(|_, entry| entry.expires > now);
+        }
+
+        // Oldest entry is evicted
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key)
+            && let Some(oldest) = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.inserted)
+                .map(|(key, _)| key.clone())
+        {
+            state.entries.remove(&oldest);
+        }
+
+        state.inserted += 1;
+        let inserted = state.inserted;
+
+        state.entries.insert(key, CacheEntry { value, expires: now + ttl, inserted });
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        self.lock().entries.remove(key);
+    }
+
+    pub(crate) fn remove_gate(&self, gate: &str) {
+        let prefix = cache_key(gate, "");
+
+        self.lock().entries.retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let cache = ResponseCache::new(4);
+        cache.insert(cache_key("Get", "1"), json!(1), Duration::from_millis(20));
+        cache.insert(cache_key("Get", "2"), json!(2), Duration::from_secs(60));
+
+        assert_eq!(cache.get("TestCacheTtl", &cache_key("Get", "1")), Some(json!(1)));
+        sleep(Duration::from_millis(30));
+        assert_eq!(cache.get("TestCacheTtl", &cache_key("Get", "1")), None);
+        assert_eq!(cache.get("TestCacheTtl", &cache_key("Get", "2")), Some(json!(2)));
+        assert_eq!(cache.lock().entries.len(), 1);
+    }
+
+    #[test]
+    fn oldest_entry_is_evicted_over_capacity() {
+        let cache = ResponseCache::new(2);
+        for (request, value) in [("1", 1), ("2", 2), ("3", 3)] {
+            cache.insert(cache_key("Get", request), json!(value), Duration::from_secs(60));
+        }
+
+        assert_eq!(cache.get("TestCacheCapacity", &cache_key("Get", "1")), None);
+        assert_eq!(cache.get("TestCacheCapacity", &cache_key("Get", "3")), Some(json!(3)));
+
+        // Replacing an entry does not evict
+        cache.insert(cache_key("Get", "2"), json!(4), Duration::from_secs(60));
+        assert_eq!(cache.get("TestCacheCapacity", &cache_key("Get", "2")), Some(json!(4)));
+        assert_eq!(cache.get("TestCacheCapacity", &cache_key("Get", "3")), Some(json!(3)));
+    }
+
+    #[test]
+    fn expired_entries_are_evicted_first() {
+        let cache = ResponseCache::new(2);
+        cache.insert(cache_key("Get", "1"), json!(1), Duration::from_secs(60));
+        cache.insert(cache_key("Get", "2"), json!(2), Duration::ZERO);
+        cache.insert(cache_key("Get", "3"), json!(3), Duration::from_secs(60));
+
+        assert_eq!(cache.get("TestCacheExpired", &cache_key("Get", "1")), Some(json!(1)));
+        assert_eq!(cache.get("TestCacheExpired", &cache_key("Get", "3")), Some(json!(3)));
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let cache = ResponseCache::new(0);
+        cache.insert(cache_key("Get", "1"), json!(1), Duration::from_secs(60));
+
+        assert_eq!(cache.get("TestCacheZero", &cache_key("Get", "1")), None);
+    }
+
+    #[test]
+    fn invalidation_removes_entries() {
+        let cache = ResponseCache::new(8);
+        for key in [cache_key("Get", "1"), cache_key("Get", "2"), cache_key("GetAll", "1"), cache_key("List", "1")] {
+            cache.insert(key, json!(0), Duration::from_secs(60));
+        }
+
+        cache.remove(&cache_key("Get", "1"));
+        assert_eq!(cache.get("TestCacheInvalidate", &cache_key("Get", "1")), None);
+        assert!(cache.get("TestCacheInvalidate", &cache_key("Get", "2")).is_some());
+
+        // Gate prefix does not match gates with a longer name
+        cache.remove_gate("Get");
+        assert_eq!(cache.get("TestCacheInvalidate", &cache_key("Get", "2")), None);
+        assert!(cache.get("TestCacheInvalidate", &cache_key("GetAll", "1")).is_some());
+
+        cache.clear();
+        assert_eq!(cache.get("TestCacheInvalidate", &cache_key("List", "1")), None);
+    }
+
+    #[test]
+    fn hits_and_misses_are_counted() {
+        let cache = ResponseCache::new(4);
+        cache.insert(cache_key("Get", "1"), json!(1), Duration::from_secs(60));
+
+        cache.get("TestCacheCounters", &cache_key("Get", "1"));
+        cache.get("TestCacheCounters", &cache_key("Get", "1"));
+        cache.get("TestCacheCounters", &cache_key("Get", "2"));
+
+        assert_eq!(metrics::get("rmt.cache.TestCacheCounters.hit"), Some(2));
+        assert_eq!(metrics::get("rmt.cache.TestCacheCounters.miss"), Some(1));
+    }
+}
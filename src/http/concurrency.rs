@@ -0,0 +1,335 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+use actix_web::{HttpMessage, HttpResponse, body::BoxBody, dev::{self, ServiceResponse}, middleware, web};
+use log::info;
+use tokio::sync::oneshot;
+
+use crate::{Error, common_gate::GateErrorResponse, error::ServiceError, http::GateMeta};
+
+/** Gate priority. Lower priority requests are shed first */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    Critical
+}
+
+/** Maximum of requests in flight and size of the wait queue.
+    ```max_in_flight``` 0 - unlimited
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Concurrency {
+    pub max_in_flight: usize,
+    pub queue: usize
+}
+
+impl Concurrency {
+    pub const fn new(max_in_flight: usize, queue: usize) -> Self {
+        Self { max_in_flight, queue }
+    }
+}
+
+// Cancelled waiter drops the handed permit, so the slot is not lost
+struct Waiter {
+    priority: Priority,
+    sender: oneshot::Sender<Permit>
+}
+
+struct PoolState {
+    in_flight: usize,
+    waiters: Vec<Waiter>
+}
+
+// Slots of in flight requests with a bounded priority queue
+struct Pool {
+    limit: Concurrency,
+    state: Mutex<PoolState>
+}
+
+// Slot is handed to the next waiter on drop
+struct Permit {
+    pool: Option<Arc<Pool>>
+}
+
+/** Slots held by a request. Job gates take them out of the request,
+    so the slots are held until the job finishes
+ */
+pub(crate) struct Permits {
+    _held: Vec<Permit>
+}
+
+impl Pool {
+    fn new(limit: Concurrency) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(PoolState { in_flight: 0, waiters: Vec::new() })
+        }
+    }
+
+    async fn acquire(self: &Arc<Self>, priority: Priority) -> Option<Permit> {
+        let receiver = {
+            let mut state = self.state.lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            if state.in_flight < self.limit.max_in_flight {
+                state.in_flight += 1;
+                return Some(Permit { pool: Some(self.clone()) })
+            }
+
+            // Cancelled requests leave closed waiters
+            state.waiters.retain(|waiter| !waiter.sender.is_closed());
+
+            if state.waiters.len() >= self.limit.queue {
+                // The latest of lowest priority waiters is shed in favor of the new one
+                let shed = state.waiters.iter()
+                    .enumerate()
+                    .filter(|(_, waiter)| waiter.priority < priority)
+                    .min_by_key(|(index, waiter)| (waiter.priority, std::cmp::Reverse(*index)))
+                    .map(|(index, _)| index)?;
+
+                // Dropped sender rejects the waiter
+                state.waiters.remove(shed);
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.push(Waiter { priority, sender });
+            receiver
+        };
+
+        receiver.await.ok()
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Highest priority first, in order of arrival
+        while let Some(next) = state.waiters.iter()
+            .enumerate()
+            .max_by_key(|(index, waiter)| (waiter.priority, std::cmp::Reverse(*index)))
+            .map(|(index, _)| index)
+        {
+            let permit = Permit { pool: Some(self.clone()) };
+
+            match state.waiters.remove(next).sender.send(permit) {
+                Ok(()) => return,
+                // Returned permit must not release the slot again
+                Err(mut permit) => { permit.pool.take(); }
+            }
+        }
+
+        state.in_flight -= 1;
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release();
+        }
+    }
+}
+
+/** **Concurrency Limits**
+
+    Gate limits are set with ```max_in_flight```, ```queue``` and ```priority``` gate options,
+    or overriden on the Instance. Instance limit is shared by all gates.
+    Requests over the queue are answered with ```503```
+ */
+#[derive(Default)]
+pub(crate) struct ConcurrencyLimits {
+    shared: Option<Arc<Pool>>,
+    overrides: HashMap<String, Concurrency>,
+    gates: Mutex<HashMap<String, Arc<Pool>>>
+}
+
+impl ConcurrencyLimits {
+    pub(crate) fn set_shared(&mut self, limit: Concurrency) {
+        self.shared = Some(Arc::new(Pool::new(limit)));
+    }
+
+    pub(crate) fn set_gate(&mut self, gate: &str, limit: Concurrency) {
+        self.overrides.insert(gate.to_string(), limit);
+    }
+
+    // Gate pools and priorities need the gate name from the request body
+    pub(crate) fn limits_gates(&self, gates: &[GateMeta]) -> bool {
+        self.shared.as_ref().is_some_and(|pool| pool.limit.max_in_flight != 0)
+            || !self.overrides.is_empty()
+            || gates.iter().any(|gate| gate.max_in_flight != 0)
+    }
+
+    fn gate_pool(&self, path: &str, gate: &GateMeta) -> Option<Arc<Pool>> {
+        let limit = self.overrides.get(gate.name)
+            .copied()
+            .unwrap_or(Concurrency::new(gate.max_in_flight, gate.queue));
+
+        if limit.max_in_flight == 0 {
+            return None
+        }
+
+        let mut gates = self.gates.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        Some(gates.entry(format!("{path}:{}", gate.name))
+            .or_insert_with(|| Arc::new(Pool::new(limit)))
+            .clone())
+    }
+
+    async fn acquire(&self, path: &str, gate: Option<&GateMeta>) -> Option<Vec<Permit>> {
+        let priority = gate.map(|gate| gate.priority).unwrap_or(Priority::Normal);
+        let mut permits = Vec::new();
+
+        if let Some(pool) = gate.and_then(|gate| self.gate_pool(path, gate)) {
+            permits.push(pool.acquire(priority).await?);
+        }
+
+        if let Some(pool) = &self.shared
+            && pool.limit.max_in_flight != 0
+        {
+            permits.push(pool.acquire(priority).await?);
+        }
+
+        Some(permits)
+    }
+}
+
+pub(crate) async fn concurrency_middleware(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+{
+    let Some(limits) = request.app_data::<web::Data<ConcurrencyLimits>>().cloned() else {
+        return next.call(request).await
+    };
+
+    let gate = request.extensions().get::<GateMeta>().copied();
+
+    let Some(permits) = limits.acquire(request.path(), gate.as_ref()).await else {
+        info!("Request to {} is shed", request.path());
+
+        return Ok(request.into_response(
+            HttpResponse::ServiceUnavailable()
+                .json(GateErrorResponse::from(Error::Service(ServiceError::Overloaded)))
+        ));
+    };
+
+    request.extensions_mut().insert(Permits { _held: permits });
+    let response = next.call(request).await;
+
+    // Slots not taken by a job are released with the response
+    if let Ok(response) = &response {
+        response.request().extensions_mut().remove::<Permits>();
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::task::yield_now;
+
+    use super::*;
+
+    fn pool(max_in_flight: usize, queue: usize) -> Arc<Pool> {
+        Arc::new(Pool::new(Concurrency::new(max_in_flight, queue)))
+    }
+
+    fn waiters(pool: &Pool) -> usize {
+        pool.state.lock().unwrap().waiters.len()
+    }
+
+    // Waiter which records the order permits are handed out
+    async fn wait(pool: Arc<Pool>, priority: Priority, id: usize, order: Arc<Mutex<Vec<usize>>>) -> bool {
+        let permit = pool.acquire(priority).await;
+        order.lock().unwrap().push(id);
+        permit.is_some()
+    }
+
+    #[tokio::test]
+    async fn permits_up_to_limit() {
+        let pool = pool(2, 1);
+
+        let first = pool.acquire(Priority::Normal).await;
+        let second = pool.acquire(Priority::Normal).await;
+        assert!(first.is_some() && second.is_some());
+        assert_eq!(pool.state.lock().unwrap().in_flight, 2);
+
+        drop(first);
+        drop(second);
+        assert_eq!(pool.state.lock().unwrap().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn waiters_are_served_by_priority_then_arrival() {
+        let pool = pool(1, 4);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let permit = pool.acquire(Priority::Normal).await;
+
+        let mut tasks = Vec::new();
+        for (id, priority) in [Priority::Low, Priority::Normal, Priority::Critical, Priority::Normal].into_iter().enumerate() {
+            tasks.push(tokio::spawn(wait(pool.clone(), priority, id, order.clone())));
+            yield_now().await;
+        }
+        assert_eq!(waiters(&pool), 4);
+
+        drop(permit);
+        for task in tasks {
+            assert!(task.await.unwrap());
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec![2, 1, 3, 0]);
+        assert_eq!(pool.state.lock().unwrap().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn lower_priority_waiter_is_shed() {
+        let pool = pool(1, 1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let permit = pool.acquire(Priority::Normal).await;
+
+        let low = tokio::spawn(wait(pool.clone(), Priority::Low, 0, order.clone()));
+        yield_now().await;
+        let critical = tokio::spawn(wait(pool.clone(), Priority::Critical, 1, order.clone()));
+        yield_now().await;
+
+        // Low waiter is rejected at once, critical one takes its place
+        assert!(!low.await.unwrap());
+        assert_eq!(waiters(&pool), 1);
+
+        drop(permit);
+        assert!(critical.await.unwrap());
+        assert_eq!(*order.lock().unwrap(), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn request_is_shed_when_queue_is_full() {
+        let pool = pool(1, 1);
+        let _permit = pool.acquire(Priority::Normal).await;
+
+        let _queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire(Priority::Normal).await.is_some() }
+        });
+        yield_now().await;
+
+        assert!(pool.acquire(Priority::Normal).await.is_none());
+        assert!(pool.acquire(Priority::Low).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_keeps_the_slot() {
+        let pool = pool(1, 1);
+        let permit = pool.acquire(Priority::Normal).await;
+
+        let cancelled = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.acquire(Priority::Normal).await.is_some() }
+        });
+        yield_now().await;
+        cancelled.abort();
+        let _ = cancelled.await;
+
+        drop(permit);
+        assert_eq!(pool.state.lock().unwrap().in_flight, 0);
+        assert!(pool.acquire(Priority::Low).await.is_some());
+    }
+}
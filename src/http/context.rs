@@ -1,16 +1,31 @@
-use std::{marker::PhantomData, time::Duration};
-use crate::{Error, Origin, error::ServiceError, http::{self, gate::{Gate, Service}}};
+use std::{marker::PhantomData, time::{Duration, Instant}};
+use log::debug;
+use serde::de::DeserializeOwned;
+use crate::{Error, Origin, common_gate::GateErrorResponse, error::ServiceError, http::{self, Balancer, CircuitBreaker, balancer::ReplicaCall, breaker::BreakerCall, cache::{ResponseCache, cache_key}, idempotency::{IDEMPOTENCY_KEY_HEADER, idempotency_key}, jobs::JobStatus, request::{DEADLINE_HEADER, TRACE_ID_HEADER, Trace}, version::VERSION_HEADER, gate::{GateDefinition, GateMeta, Service}}};
 
-const SERVICE_REQUEST_TIMEOUT: u64 = 5000;  // 5 seconds
+pub(crate) const SERVICE_REQUEST_TIMEOUT: u64 = 5000;  // 5 seconds
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/** Jobs are waited for this long, unless set by ```Context::job_timeout``` */
+pub const JOB_WAIT_TIMEOUT: Duration = Duration::from_secs(600);
+const RESPONSE_CACHE_CAPACITY: usize = 1024;
 
 /** **Service Context**
 
     describes a microservice origin and binded service. 
+    Internal flag is the default access of service gates.
  */
 pub struct Context<S: Service> {
     pub(crate) origin: Origin,
     pub(crate) phantom: PhantomData<S>,
-    pub(crate) internal: bool
+    pub(crate) internal: bool,
+    pub(crate) mount: &'static str,
+    pub(crate) internal_port: Option<u16>,
+    pub(crate) breaker: Option<CircuitBreaker>,
+    pub(crate) balancer: Option<&'static Balancer>,
+    pub(crate) attempts: u32,
+    pub(crate) backoff: Duration,
+    pub(crate) job_timeout: Duration,
+    pub(crate) cache: ResponseCache
 }
 
 impl<S: Service> Context<S> {
@@ -18,37 +33,592 @@ impl<S: Service> Context<S> {
         Self {
             origin,
             phantom: PhantomData,
-            internal
+            internal,
+            mount: "",
+            internal_port: None,
+            breaker: None,
+            balancer: None,
+            attempts: 1,
+            backoff: Duration::ZERO,
+            job_timeout: JOB_WAIT_TIMEOUT,
+            cache: ResponseCache::new(RESPONSE_CACHE_CAPACITY)
         }
     }
 
+    /** Mounts the service under a path prefix. 
+        Required when several services are hosted by one Instance
+        ```ignore
+        http_context![ ::2020 ].mount("/users")
+        ```
+     */
+    pub const fn mount(mut self, path: &'static str) -> Self {
+        self.mount = path;
+        self
+    }
+
+    /** Serves internal gates on a separate port */
+    pub const fn internal_port(mut self, port: u16) -> Self {
+        self.internal_port = Some(port);
+        self
+    }
+
+    /** Fails calls fast while the service is down. With a balancer, replicas have own circuits instead */
+    pub const fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    pub fn breaker(&self) -> Option<&CircuitBreaker> {
+        self.breaker.as_ref()
+    }
+
+    /** Balances calls across service replicas. 
+        Context origin is still used by the Instance to bind
+     */
+    pub const fn balancer(mut self, balancer: &'static Balancer) -> Self {
+        self.balancer = Some(balancer);
+        self
+    }
+
+    /** Attempts of a request and the first backoff, doubled after every failed attempt.
+        Default is a single attempt. Attempts of a request carry the same idempotency key,
+        so they are processed once by an instance with the idempotency window
+     */
+    pub const fn retries(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.attempts = if attempts == 0 { 1 } else { attempts };
+        self.backoff = backoff;
+        self
+    }
+
+    /** Longest wait for a job to finish, then the wait fails with a timeout.
+        The job keeps running. Default 10 minutes
+     */
+    pub const fn job_timeout(mut self, timeout: Duration) -> Self {
+        self.job_timeout = timeout;
+        self
+    }
+
+    /** Maximum of cached responses. Default 1024 */
+    pub const fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache.capacity = capacity;
+        self
+    }
+
     pub fn origin(&'static self) -> &'static Origin {
         &self.origin
     }
 
-    /** Make request to a microservice by using context */
+    /** Path prefix the service is mounted under */
+    pub fn mount_path(&self) -> &'static str {
+        self.mount
+    }
+
+    /** Port of public or internal gates */
+    pub fn port(&self, internal: bool) -> u16 {
+        match (internal, self.internal_port) {
+            (true, Some(port)) => port,
+            _ => self.origin.port()
+        }
+    }
+
+    /** Resolves gate access */
+    pub fn is_internal(&self, gate: &GateMeta) -> bool {
+        gate.access.is_internal(self.internal)
+    }
+
+    /** Full route of public or internal gates, including the mount path */
+    pub fn route(&self, internal: bool) -> String {
+        route(self.mount, internal)
+    }
+
+    /** Make request to a microservice by using context.
+        Responses of gates with ```cache_ttl``` option are cached.
+        Request is sent with a new idempotency key, reused by its retries
+     */
     pub async fn request<G>(&self, http_client: reqwest::Client, gate: G) 
         -> Result<G::Response, Error> 
     where 
-        G: Gate + Into<<S as Service>::Requests>,
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
     {
-        let path = if self.internal { "internal-request" } else { "request" };
-        
-        let raw = http_client.post(format!("http://{}:{}/{path}", self.origin.host(), self.origin.port()))
-            .json(&Into::<S::Requests>::into(gate))
+        self.request_with_key(http_client, gate, &idempotency_key()).await
+    }
+
+    /** Make request with an idempotency key. 
+        Retries with the same key replay the first result if Instance remembers it.
+        Job gates are started and polled until they finish
+     */
+    pub async fn request_with_key<G>(&self, http_client: reqwest::Client, gate: G, idempotency_key: &str)
+        -> Result<G::Response, Error>
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        if G::META.job {
+            let job = self.start_job_with_key(http_client.clone(), gate, idempotency_key).await?;
+            return self.wait_job::<G>(http_client, &job.id).await
+        }
+
+        let request: S::Requests = gate.into();
+
+        let key = match self.cached::<G>(&request) {
+            Ok(response) => return Ok(response),
+            Err(key) => key
+        };
+
+        let mut attempt = 1;
+        let response = loop {
+            let response = match self.begin() {
+                Ok(call) => {
+                    let response = self.send::<G, G::Response>(http_client.clone(), call.origin(&self.origin), &request, idempotency_key).await;
+                    call.finish(&response);
+                    response
+                },
+                Err(err) => Err(err)
+            };
+
+            match self.retry_wait(attempt, &response) {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => break response
+            }
+            attempt += 1;
+        };
+
+        self.store::<G>(key, &response);
+        response
+    }
+
+    /** Blocking request for synchronous callers.
+        Must not be called inside of an async runtime, see ```reqwest::blocking```
+     */
+    pub fn request_blocking<G>(&self, http_client: &reqwest::blocking::Client, gate: G)
+        -> Result<G::Response, Error>
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        self.request_blocking_with_key(http_client, gate, &idempotency_key())
+    }
+
+    /** Blocking request with an idempotency key */
+    pub fn request_blocking_with_key<G>(&self, http_client: &reqwest::blocking::Client, gate: G, idempotency_key: &str)
+        -> Result<G::Response, Error>
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        let request: S::Requests = gate.into();
+
+        if G::META.job {
+            let job: JobStatus = self.send_blocking::<G, _>(http_client, &self.origin, &request, idempotency_key)?;
+            let url = self.job_url::<G>(&job.id);
+            let deadline = Instant::now() + self.job_timeout;
+
+            loop {
+                let raw = http_client.get(&url)
+                    .timeout(Duration::from_millis(SERVICE_REQUEST_TIMEOUT))
+                    .send()
+                    .map_err(request_error)?;
+
+                let (status, headers) = (raw.status(), raw.headers().clone());
+                let body = raw.bytes().map_err(request_error)?;
+
+                if let Some(response) = job_response(status, &headers, &body, S::VERSION)?.response()? {
+                    return Ok(response)
+                }
+                std::thread::sleep(poll_wait(deadline)?);
+            }
+        }
+
+        let key = match self.cached::<G>(&request) {
+            Ok(response) => return Ok(response),
+            Err(key) => key
+        };
+
+        let mut attempt = 1;
+        let response = loop {
+            let response = match self.begin() {
+                Ok(call) => {
+                    let response = self.send_blocking::<G, G::Response>(http_client, call.origin(&self.origin), &request, idempotency_key);
+                    call.finish(&response);
+                    response
+                },
+                Err(err) => Err(err)
+            };
+
+            match self.retry_wait(attempt, &response) {
+                Some(wait) => std::thread::sleep(wait),
+                None => break response
+            }
+            attempt += 1;
+        };
+
+        self.store::<G>(key, &response);
+        response
+    }
+
+    /** Starts a job gate and returns the running job.
+        Jobs are kept by the instance which started them,
+        so job calls go to the context origin without balancing
+     */
+    pub async fn start_job<G>(&self, http_client: reqwest::Client, gate: G) -> Result<JobStatus, Error>
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        self.start_job_with_key(http_client, gate, &idempotency_key()).await
+    }
+
+    /** Starts a job gate with an idempotency key. Retries with the same key return the same job */
+    pub async fn start_job_with_key<G>(&self, http_client: reqwest::Client, gate: G, idempotency_key: &str)
+        -> Result<JobStatus, Error>
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        if !G::META.job {
+            return Err(Error::Service(ServiceError::WrongGate))
+        }
+
+        self.send::<G, _>(http_client, &self.origin, &gate.into(), idempotency_key).await
+    }
+
+    /** Current state of a job, with the result when finished */
+    pub async fn job_status<G>(&self, http_client: reqwest::Client, id: &str) -> Result<JobStatus, Error>
+    where
+        G: GateDefinition<Service = S>,
+    {
+        self.job_call(http_client.get(self.job_url::<G>(id))).await
+    }
+
+    /** Cancels a running job */
+    pub async fn cancel_job<G>(&self, http_client: reqwest::Client, id: &str) -> Result<JobStatus, Error>
+    where
+        G: GateDefinition<Service = S>,
+    {
+        self.job_call(http_client.delete(self.job_url::<G>(id))).await
+    }
+
+    /** Polls the job until it finishes or ```job_timeout``` passes */
+    pub async fn wait_job<G>(&self, http_client: reqwest::Client, id: &str) -> Result<G::Response, Error>
+    where
+        G: GateDefinition<Service = S>,
+    {
+        let deadline = Instant::now() + self.job_timeout;
+
+        loop {
+            if let Some(response) = self.job_status::<G>(http_client.clone(), id).await?.response()? {
+                return Ok(response)
+            }
+            tokio::time::sleep(poll_wait(deadline)?).await;
+        }
+    }
+
+    fn job_url<G: GateDefinition<Service = S>>(&self, id: &str) -> String {
+        format!("{}/jobs/{}", self.url::<G>(&self.origin), id)
+    }
+
+    async fn job_call(&self, request: reqwest::RequestBuilder) -> Result<JobStatus, Error> {
+        let raw = request
             .timeout(Duration::from_millis(SERVICE_REQUEST_TIMEOUT))
             .send()
             .await
-            .map_err(|err| {
-                if err.is_timeout() {
-                    Error::Service(ServiceError::ServiceRequestTimeout)
-                } else {
-                    Error::Http(http::error::Error::Text(err.to_string()))
-                }
-            })?;
+            .map_err(request_error)?;
+
+        let (status, headers) = (raw.status(), raw.headers().clone());
+        let body = raw.bytes().await.map_err(request_error)?;
 
-        raw.json().await
+        job_response(status, &headers, &body, S::VERSION)
+    }
+
+    /** Removes cached response of the request */
+    pub fn invalidate<G>(&self, gate: G)
+    where
+        G: GateDefinition<Service = S> + Into<<S as Service>::Requests>,
+    {
+        if let Ok(json) = serde_json::to_string(&Into::<S::Requests>::into(gate)) {
+            self.cache.remove(&cache_key(G::META.name, &json));
+        }
+    }
+
+    /** Removes cached responses of the gate */
+    pub fn invalidate_gate<G: GateDefinition<Service = S>>(&self) {
+        self.cache.remove_gate(G::META.name);
+    }
+
+    /** Removes all cached responses */
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    // Cached response, or the cache key of gates with cache_ttl option
+    fn cached<G>(&self, request: &S::Requests) -> Result<G::Response, Option<String>>
+    where
+        G: GateDefinition<Service = S>,
+    {
+        let key = (!G::META.cache_ttl.is_zero())
+            .then(|| serde_json::to_string(request).ok())
+            .flatten()
+            .map(|json| cache_key(G::META.name, &json));
+
+        match key.as_ref().and_then(|key| self.cache.get(S::NAME, key)) {
+            Some(value) => serde_json::from_value(value).map_err(|_| key),
+            None => Err(key)
+        }
+    }
+
+    fn store<G>(&self, key: Option<String>, response: &Result<G::Response, Error>)
+    where
+        G: GateDefinition<Service = S>,
+    {
+        if let (Some(key), Ok(response)) = (key, response)
+            && let Ok(value) = serde_json::to_value(response)
+        {
+            self.cache.insert(key, value, G::META.cache_ttl);
+        }
+    }
+
+    // Backoff before the next attempt, none when the request is not retried
+    fn retry_wait<R>(&self, attempt: u32, response: &Result<R, Error>) -> Option<Duration> {
+        let err = response.as_ref().err()?;
+        let retried = is_failure(err) || matches!(err, Error::Service(ServiceError::RateLimited { .. }));
+
+        if attempt >= self.attempts || !retried {
+            return None
+        }
+
+        let backoff = self.backoff.saturating_mul(1 << (attempt - 1).min(16));
+        debug!("Request to {} failed, attempt {} of {}. {}", S::NAME, attempt, self.attempts, err);
+
+        match err {
+            Error::Service(ServiceError::RateLimited { retry_after: Some(retry_after) }) => Some(backoff.max(*retry_after)),
+            _ => Some(backoff)
+        }
+    }
+
+    // Call through a replica of the balancer, or the circuit breaker of the service
+    pub(crate) fn begin(&self) -> Result<Call<'_>, Error> {
+        if let Some(balancer) = self.balancer.filter(|balancer| !balancer.origins().is_empty()) {
+            let replica = balancer.acquire(S::NAME)
+                .ok_or(Error::Service(ServiceError::CircuitOpen))?;
+
+            return Ok(Call { breaker: None, replica: Some(replica) })
+        }
+
+        let breaker = match &self.breaker {
+            Some(breaker) => Some(
+                breaker.acquire(S::NAME)
+                    .ok_or(Error::Service(ServiceError::CircuitOpen))?
+            ),
+            None => None
+        };
+
+        Ok(Call { breaker, replica: None })
+    }
+
+    fn url<G: GateDefinition<Service = S>>(&self, origin: &Origin) -> String {
+        let internal = self.is_internal(&G::META);
+        let port = match (internal, self.internal_port) {
+            (true, Some(port)) => port,
+            _ => origin.port()
+        };
+
+        format!("http://{}:{}{}", origin.host(), port, self.route(internal))
+    }
+
+    async fn send<G, T>(&self, http_client: reqwest::Client, origin: &Origin, request: &S::Requests, idempotency_key: &str)
+        -> Result<T, Error>
+    where
+        G: GateDefinition<Service = S>,
+        T: DeserializeOwned,
+    {
+        let (headers, timeout) = trace_headers()?;
+
+        let raw = http_client.post(self.url::<G>(origin))
+            .json(request)
+            .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+            .header(VERSION_HEADER, S::VERSION)
+            .headers(headers)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        let (status, headers) = (raw.status(), raw.headers().clone());
+        let body = raw.bytes().await.map_err(request_error)?;
+
+        gate_response(status, &headers, &body, S::VERSION)
+    }
+
+    fn send_blocking<G, T>(&self, http_client: &reqwest::blocking::Client, origin: &Origin, request: &S::Requests, idempotency_key: &str)
+        -> Result<T, Error>
+    where
+        G: GateDefinition<Service = S>,
+        T: DeserializeOwned,
+    {
+        let (headers, timeout) = trace_headers()?;
+
+        let raw = http_client.post(self.url::<G>(origin))
+            .json(request)
+            .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+            .header(VERSION_HEADER, S::VERSION)
+            .headers(headers)
+            .timeout(timeout)
+            .send()
+            .map_err(request_error)?;
+
+        let (status, headers) = (raw.status(), raw.headers().clone());
+        let body = raw.bytes().map_err(request_error)?;
+
+        gate_response(status, &headers, &body, S::VERSION)
+    }
+}
+
+/** Route of public or internal gates of a service mounted under the path */
+pub fn route(mount: &str, internal: bool) -> String {
+    let endpoint = if internal { "internal-request" } else { "request" };
+
+    match mount.trim_matches('/') {
+        "" => format!("/{endpoint}"),
+        mount => format!("/{mount}/{endpoint}")
+    }
+}
+
+// Breaker and replica guards of a call
+pub(crate) struct Call<'a> {
+    breaker: Option<BreakerCall<'a>>,
+    replica: Option<ReplicaCall<'a>>
+}
+
+impl<'a> Call<'a> {
+    pub(crate) fn origin(&self, origin: &'a Origin) -> &'a Origin {
+        self.replica.as_ref()
+            .map(|replica| replica.origin())
+            .unwrap_or(origin)
+    }
+
+    pub(crate) fn finish<R>(self, response: &Result<R, Error>) {
+        let success = !response.as_ref().is_err_and(is_failure);
+
+        if let Some(replica) = self.replica {
+            replica.record(success);
+        }
+
+        if let Some(breaker) = self.breaker {
+            breaker.record(success);
+        }
+    }
+}
+
+pub(crate) fn request_error(err: reqwest::Error) -> Error {
+    if err.is_timeout() {
+        Error::Service(ServiceError::ServiceRequestTimeout)
+    } else {
+        Error::Http(http::error::Error::Text(err.to_string()))
+    }
+}
+
+// Decodes the response of a gate call, shared by async and blocking calls
+fn gate_response<T: DeserializeOwned>(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: &[u8], version: &str)
+    -> Result<T, Error>
+{
+    let parse_error = |err: serde_json::Error| Error::Service(ServiceError::JSONParseError(err.to_string()));
+
+    if status == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+        let response: GateErrorResponse = serde_json::from_slice(body).map_err(parse_error)?;
+        return Err(Error::Service(ServiceError::Validation(response.fields)))
+    }
+
+    if let Some(err) = status_error(status, headers, version) {
+        return Err(err)
+    }
+
+    // Gate errors are answered with 200 too, responses are tagged with the gate
+    let value: serde_json::Value = serde_json::from_slice(body).map_err(parse_error)?;
+    if value.get("gate").is_none() && value.get("error").is_some() {
+        let response: GateErrorResponse = serde_json::from_value(value).map_err(parse_error)?;
+        return Err(Error::Service(ServiceError::GateFailed(response.error)))
+    }
+
+    serde_json::from_value(value).map_err(parse_error)
+}
+
+// Inside of a gate calls forward the trace id and do not outlive the deadline of the gate request
+fn trace_headers() -> Result<(reqwest::header::HeaderMap, Duration), Error> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let mut timeout = Duration::from_millis(SERVICE_REQUEST_TIMEOUT);
+
+    if let Some(trace) = Trace::current() {
+        if let Ok(trace_id) = reqwest::header::HeaderValue::from_str(&trace.trace_id) {
+            headers.insert(TRACE_ID_HEADER, trace_id);
+        }
+
+        if let Some(deadline) = trace.deadline {
+            timeout = timeout.min(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    if timeout.is_zero() {
+        return Err(Error::Service(ServiceError::ServiceRequestTimeout))
+    }
+
+    headers.insert(DEADLINE_HEADER, reqwest::header::HeaderValue::from(timeout.as_millis() as u64));
+    Ok((headers, timeout))
+}
+
+// Job routes answer 404 for unknown or expired jobs
+fn job_response(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: &[u8], version: &str) -> Result<JobStatus, Error> {
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::Service(ServiceError::JobNotFound))
+    }
+
+    if let Some(err) = status_error(status, headers, version) {
+        return Err(err)
+    }
+
+    serde_json::from_slice(body)
         .map_err(|err| Error::Service(ServiceError::JSONParseError(err.to_string())))
+}
+
+// Next poll of a job, the wait times out at the deadline
+fn poll_wait(deadline: Instant) -> Result<Duration, Error> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    if remaining.is_zero() {
+        return Err(Error::Service(ServiceError::ServiceRequestTimeout))
+    }
+    Ok(remaining.min(JOB_POLL_INTERVAL))
+}
+
+// Errors of unavailable service, counted by circuit breaker
+fn is_failure(err: &Error) -> bool {
+    matches!(err,
+        Error::Http(_)
+        | Error::Service(ServiceError::ServiceRequestTimeout)
+        | Error::Service(ServiceError::Overloaded)
+    )
+}
+
+// Maps rejections of service instance
+pub(crate) fn status_error(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, version: &str) -> Option<Error> {
+    match status {
+        status if status.is_success() => None,
+        reqwest::StatusCode::PRECONDITION_FAILED => {
+            let served = headers
+                .get(VERSION_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+
+            Some(Error::Service(ServiceError::IncompatibleVersion { requested: version.to_string(), served }))
+        },
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs);
+
+            Some(Error::Service(ServiceError::RateLimited { retry_after }))
+        },
+        reqwest::StatusCode::SERVICE_UNAVAILABLE => Some(Error::Service(ServiceError::Overloaded)),
+        reqwest::StatusCode::FORBIDDEN => Some(Error::Service(ServiceError::WrongRoute)),
+        reqwest::StatusCode::UNAUTHORIZED => Some(Error::Service(ServiceError::Unauthorized)),
+        status => Some(Error::Http(http::error::Error::Text(status.to_string())))
     }
 }
 
@@ -71,12 +641,19 @@ macro_rules! http_request {
 
     Examples:
     ```
-    [ ::2020 ]
-    [ (i) ::2020 ]
-    [ "126.92.24.2":2020 ]
-    [ (i) "126.92.24.2":2020 ]
-    [ H"my.website":2020 ]
-    [ (i) H"my.website":2020 ]
+    # use rmt::{http_context, http_gates};
+    # http_gates!(MyService [ Ping { request: { }, response: { } } ]);
+    # type Context = rmt::http::Context<MyService>;
+    static LOCAL: Context = http_context![ ::2020 ];
+    static LOCAL_INTERNAL: Context = http_context![ (i) ::2020 ];
+    static REMOTE: Context = http_context![ "126.92.24.2":2020 ];
+    static REMOTE_INTERNAL: Context = http_context![ (i) "126.92.24.2":2020 ];
+    static HOST: Context = http_context![ H "my.website":2020 ];
+    static HOST_INTERNAL: Context = http_context![ (i) H "my.website":2020 ];
+    # fn main() {
+    assert_eq!(LOCAL.origin().port(), 2020);
+    assert_eq!(REMOTE.origin().host(), "126.92.24.2");
+    # }
     ```
     `(i)` marks internal
  */
@@ -112,4 +689,70 @@ macro_rules! http_context {
     ] => {
         $crate::http::Context::new($crate::Origin::Host { host: $host, port: $port }, true)
     }
-}
\ No newline at end of file
+}
+// Generated clients of the test service are not used
+#[cfg(test)]
+#[allow(dead_code)]
+mod tests {
+    use crate::http_gates;
+    use super::*;
+
+    http_gates!(CacheService [
+        Get {
+            request: { key: String },
+            response: { value: Option<String> },
+            cache_ttl: Duration::from_secs(60)
+        },
+        Put {
+            request: { key: String },
+            response: { }
+        }
+    ]);
+
+    static CONTEXT: Context<CacheService> = http_context![ ::2020 ];
+
+    fn get(key: &str) -> <CacheService as Service>::Requests {
+        RMTHTTPCacheServiceGetReq { key: key.to_string() }.into()
+    }
+
+    #[test]
+    fn responses_are_cached_for_gates_with_ttl() {
+        let key = CONTEXT.cached::<RMTHTTPCacheServiceGetReq>(&get("a")).err().flatten();
+        assert!(key.is_some());
+
+        CONTEXT.store::<RMTHTTPCacheServiceGetReq>(key, &Ok(RMTHTTPCacheServiceGetRes { value: Some("1".to_string()) }));
+        let cached = CONTEXT.cached::<RMTHTTPCacheServiceGetReq>(&get("a")).ok().and_then(|response| response.value);
+        assert_eq!(cached.as_deref(), Some("1"));
+
+        CONTEXT.invalidate(RMTHTTPCacheServiceGetReq { key: "a".to_string() });
+        assert!(CONTEXT.cached::<RMTHTTPCacheServiceGetReq>(&get("a")).is_err());
+    }
+
+    #[test]
+    fn gate_without_ttl_is_not_cached() {
+        let request = RMTHTTPCacheServicePutReq { key: "a".to_string() }.into();
+
+        let key = CONTEXT.cached::<RMTHTTPCacheServicePutReq>(&request).err().flatten();
+        assert!(key.is_none());
+
+        CONTEXT.store::<RMTHTTPCacheServicePutReq>(key, &Ok(RMTHTTPCacheServicePutRes { }));
+        assert!(CONTEXT.cached::<RMTHTTPCacheServicePutReq>(&request).is_err());
+    }
+
+    #[test]
+    fn gate_error_is_not_a_response() {
+        let headers = reqwest::header::HeaderMap::new();
+        let body = serde_json::to_vec(&GateErrorResponse::from(Error::Custom(Some("broken".to_string())))).unwrap();
+
+        // Fields of the response are optional, the error body would decode into it
+        let response = gate_response::<RMTHTTPCacheServiceGetRes>(reqwest::StatusCode::OK, &headers, &body, CacheService::VERSION);
+        assert!(matches!(&response, Err(Error::Service(ServiceError::GateFailed(error))) if error.contains("broken")));
+
+        let key = CONTEXT.cached::<RMTHTTPCacheServiceGetReq>(&get("b")).err().flatten();
+        CONTEXT.store::<RMTHTTPCacheServiceGetReq>(key, &response);
+        assert!(CONTEXT.cached::<RMTHTTPCacheServiceGetReq>(&get("b")).is_err());
+
+        let response = gate_response::<RMTHTTPCacheServiceGetRes>(reqwest::StatusCode::OK, &headers, br#"{"gate":"Get","value":"1"}"#, CacheService::VERSION);
+        assert_eq!(response.ok().and_then(|response| response.value).as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,139 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::http::{Context, Service, schema::{GateSchema, ServiceSchema}};
+
+/** Route of the instance description */
+pub const DESCRIBE_ROUTE: &str = "/describe";
+
+/** **Build Info**
+
+    Build metadata of the running binary, usually created with ```http_build_info!```
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BuildInfo {
+    pub package: String,
+    pub version: String,
+    pub rmt: String,
+    // Commit of the build, when RMT_BUILD_COMMIT is set at compile time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>
+}
+
+/** Gate with its resolved access */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GateDescription {
+    #[serde(flatten)]
+    pub schema: GateSchema,
+    pub internal: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServiceDescription {
+    pub name: String,
+    pub version: String,
+    pub mount: String,
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_port: Option<u16>,
+    pub gates: Vec<GateDescription>
+}
+
+/** Response of the describe route */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Description {
+    pub build: BuildInfo,
+    pub services: Vec<ServiceDescription>
+}
+
+impl ServiceDescription {
+    pub fn of<S: Service>(context: &Context<S>) -> Self {
+        let schema = ServiceSchema::of::<S>();
+
+        let gates = schema.gates.into_iter()
+            .map(|gate| {
+                let internal = S::GATES.iter()
+                    .find(|meta| meta.name == gate.name)
+                    .is_some_and(|meta| context.is_internal(meta));
+
+                GateDescription { schema: gate, internal }
+            })
+            .collect();
+
+        Self {
+            name: schema.name,
+            version: schema.version,
+            mount: context.mount_path().to_string(),
+            port: context.port(false),
+            internal_port: context.internal_port,
+            gates
+        }
+    }
+}
+
+impl ServiceDescription {
+    /** Schema of the described service, as written by ```ServiceSchema::write``` */
+    pub fn schema(&self) -> ServiceSchema {
+        ServiceSchema {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            gates: self.gates.iter().map(|gate| gate.schema.clone()).collect()
+        }
+    }
+}
+
+pub(crate) async fn describe(description: web::Data<Description>) -> web::Json<Description> {
+    web::Json(description.get_ref().clone())
+}
+
+/** Build info of the calling crate
+    ```ignore
+    Instance::new(worker).set_describe(http_build_info!())
+    ```
+ */
+#[macro_export]
+macro_rules! http_build_info {
+    () => {
+        $crate::http::describe::BuildInfo {
+            package: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            rmt: $crate::http::describe::RMT_VERSION.to_string(),
+            commit: option_env!("RMT_BUILD_COMMIT").map(|commit| commit.to_string())
+        }
+    };
+}
+
+/** Version of rmt the binary is built with */
+pub const RMT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Generated clients of the test service are not used
+#[cfg(test)]
+#[allow(dead_code)]
+mod tests {
+    use crate::{http_context, http_gates};
+    use super::*;
+
+    http_gates!(Described version "0.3.0" [
+        Get {
+            request: { id: u64 },
+            response: { }
+        },
+        Put {
+            request: { },
+            response: { },
+            access: Public
+        }
+    ]);
+
+    static INTERNAL: Context<Described> = http_context![ (i) ::2020 ].mount("/described").internal_port(2021);
+
+    #[test]
+    fn inherited_access_follows_context() {
+        let description = ServiceDescription::of(&INTERNAL);
+
+        assert_eq!((description.mount.as_str(), description.port, description.internal_port), ("/described", 2020, Some(2021)));
+        assert_eq!(description.gates.iter().map(|gate| (gate.schema.name.as_str(), gate.internal)).collect::<Vec<_>>(),
+            vec![("Get", true), ("Put", false)]);
+        assert_eq!(description.schema(), ServiceSchema::of::<Described>());
+    }
+}
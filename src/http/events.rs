@@ -0,0 +1,359 @@
+use std::{collections::BTreeMap, future::Future, marker::PhantomData, pin::Pin, sync::Arc, time::Duration};
+use actix_web::{HttpResponse, web};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{Error, common_gate::GateErrorResponse, error::ServiceError, metrics, http::{Context, Worker, context::{SERVICE_REQUEST_TIMEOUT, request_error, status_error}, gate::Service, idempotency::{IDEMPOTENCY_KEY_HEADER, idempotency_key}}};
+
+const DELIVERY_ATTEMPTS: u32 = 5;
+const DELIVERY_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_DELIVERY_BACKOFF: Duration = Duration::from_secs(10);
+
+/** Events of a publishing service, generated by ```http_events!``` */
+pub trait EventSet {
+    const NAME: &'static str;
+    const EVENTS: &'static [&'static str];
+}
+
+/** Typed event of a set, generated by ```http_events!``` */
+pub trait Event: Serialize + DeserializeOwned + 'static {
+    type Set: EventSet;
+    const NAME: &'static str;
+}
+
+/** Event as delivered to subscribers. Id is sent as the idempotency key of every attempt */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventEnvelope {
+    pub id: String,
+    pub source: String,
+    pub event: String,
+    pub payload: serde_json::Value
+}
+
+/** Route of events delivered to a service mounted under the path */
+pub fn events_route(mount: &str) -> String {
+    match mount.trim_matches('/') {
+        "" => "/events".to_string(),
+        mount => format!("/{mount}/events")
+    }
+}
+
+type Delivery = Pin<Box<dyn Future<Output = Result<(), Error>>>>;
+
+struct Subscriber {
+    name: &'static str,
+    deliver: Arc<dyn Fn(reqwest::Client, EventEnvelope) -> Delivery + Send + Sync>
+}
+
+impl<S: Service> Context<S> {
+    // Events are served with internal gates, through breaker and balancer
+    async fn deliver(&self, http_client: reqwest::Client, envelope: EventEnvelope) -> Result<(), Error> {
+        let call = self.begin()?;
+        let origin = call.origin(&self.origin);
+        let url = format!("http://{}:{}{}", origin.host(), self.internal_port.unwrap_or(origin.port()), events_route(self.mount));
+
+        let response = match http_client.post(url)
+            .json(&envelope)
+            .header(IDEMPOTENCY_KEY_HEADER, &envelope.id)
+            .timeout(Duration::from_millis(SERVICE_REQUEST_TIMEOUT))
+            .send()
+            .await
+        {
+            Ok(raw) if raw.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY =>
+                Err(Error::Service(ServiceError::JSONParseError(format!("{} rejected event {}", S::NAME, envelope.event)))),
+            Ok(raw) => status_error(raw.status(), raw.headers(), S::VERSION).map_or(Ok(()), Err),
+            Err(err) => Err(request_error(err))
+        };
+
+        call.finish(&response);
+        response
+    }
+}
+
+// Errors which are not fixed by retrying
+fn is_permanent(err: &Error) -> bool {
+    matches!(err,
+        Error::Service(ServiceError::JSONParseError(_))
+        | Error::Service(ServiceError::WrongRoute)
+        | Error::Service(ServiceError::Unauthorized)
+    )
+}
+
+/** **Event Publisher**
+
+    Delivers events of the set to subscribed services, one after another.
+    Delivery is at least once: failed attempts are retried with exponential backoff,
+    events which can't be delivered are logged as dead letters with the full envelope.
+    Reported as ```rmt.events.<Set>.delivered```, ```.retried``` and ```.dead_letters``` metrics
+    ```ignore
+    let publisher = Publisher::<OrderEvents>::new(reqwest::Client::new())
+        .add_subscriber(&billing::SERVICE_CONTEXT)
+        .add_subscriber(&mail::SERVICE_CONTEXT);
+
+    publisher.publish(OrderCreated { id: 1, total: 9.99 }).await?;
+    ```
+ */
+pub struct Publisher<E: EventSet> {
+    http_client: reqwest::Client,
+    subscribers: Vec<Subscriber>,
+    attempts: u32,
+    backoff: Duration,
+    phantom: PhantomData<E>
+}
+
+impl<E: EventSet> Publisher<E> {
+    pub fn new(http_client: reqwest::Client) -> Self {
+        Self {
+            http_client,
+            subscribers: Vec::new(),
+            attempts: DELIVERY_ATTEMPTS,
+            backoff: DELIVERY_BACKOFF,
+            phantom: PhantomData
+        }
+    }
+
+    /** Delivers events to the service of the context */
+    pub fn add_subscriber<S: Service + Sync>(mut self, context: &'static Context<S>) -> Self {
+        self.subscribers.push(Subscriber {
+            name: S::NAME,
+            deliver: Arc::new(move |http_client, envelope| Box::pin(context.deliver(http_client, envelope)))
+        });
+        self
+    }
+
+    /** Attempts per subscriber and the first backoff. Default 5 and 100ms */
+    pub fn set_retries(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.backoff = backoff;
+        self
+    }
+
+    /** Delivers the event to every subscriber. Errors with the last failed delivery */
+    pub async fn publish<Ev: Event<Set = E>>(&self, event: Ev) -> Result<(), Error> {
+        let envelope = EventEnvelope {
+            id: idempotency_key(),
+            source: E::NAME.to_string(),
+            event: Ev::NAME.to_string(),
+            payload: serde_json::to_value(&event)
+                .map_err(|err| Error::Service(ServiceError::JSONParseError(err.to_string())))?
+        };
+
+        let mut result = Ok(());
+        for subscriber in &self.subscribers {
+            if let Err(err) = self.deliver(subscriber, &envelope).await {
+                result = Err(err);
+            }
+        }
+
+        result
+    }
+
+    async fn deliver(&self, subscriber: &Subscriber, envelope: &EventEnvelope) -> Result<(), Error> {
+        let set = E::NAME;
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+
+        loop {
+            let err = match (subscriber.deliver)(self.http_client.clone(), envelope.clone()).await {
+                Ok(()) => {
+                    metrics::increment(&format!("rmt.events.{set}.delivered"), 1);
+                    return Ok(())
+                },
+                Err(err) => err
+            };
+
+            if attempt >= self.attempts || is_permanent(&err) {
+                metrics::increment(&format!("rmt.events.{set}.dead_letters"), 1);
+                error!("Dead letter of {} after {} attempts. {}. {}", subscriber.name, attempt, err,
+                    serde_json::to_string(envelope).unwrap_or_default());
+                return Err(err)
+            }
+
+            let wait = match &err {
+                Error::Service(ServiceError::RateLimited { retry_after: Some(retry_after) }) => backoff.max(*retry_after),
+                _ => backoff
+            };
+            debug!("Event {} to {} failed, attempt {} of {}. {}", envelope.event, subscriber.name, attempt, self.attempts, err);
+            metrics::increment(&format!("rmt.events.{set}.retried"), 1);
+
+            tokio::time::sleep(wait).await;
+            backoff = (backoff * 2).min(MAX_DELIVERY_BACKOFF);
+            attempt += 1;
+        }
+    }
+}
+
+type Handler<W> = Arc<dyn Fn(W, serde_json::Value) -> Delivery + Send + Sync>;
+
+/** **Event Subscription**
+
+    Typed handler of an event, returned by ```Worker::subscriptions```.
+    Events can be delivered more than once, handlers should tolerate duplicates.
+    Duplicates are dropped within the idempotency window when the Instance has one
+    ```ignore
+    fn subscriptions(&self) -> Vec<Subscription<Self>> {
+        vec![
+            Subscription::on(|worker: Self, event: OrderCreated| async move { worker.bill(event.id).await })
+        ]
+    }
+    ```
+ */
+pub struct Subscription<W> {
+    key: String,
+    handler: Handler<W>
+}
+
+fn subscription_key(source: &str, event: &str) -> String {
+    format!("{source}/{event}")
+}
+
+impl<W: 'static> Subscription<W> {
+    pub fn on<Ev, F, R>(handler: F) -> Self
+    where
+        Ev: Event,
+        F: Fn(W, Ev) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), Error>> + 'static
+    {
+        let handler = Arc::new(handler);
+
+        Self {
+            key: subscription_key(Ev::Set::NAME, Ev::NAME),
+            handler: Arc::new(move |worker, payload| {
+                let handler = handler.clone();
+                Box::pin(async move {
+                    let event = serde_json::from_value(payload)
+                        .map_err(|err| Error::Service(ServiceError::JSONParseError(err.to_string())))?;
+
+                    handler(worker, event).await
+                })
+            })
+        }
+    }
+}
+
+// Handlers of a mounted worker by source and event
+pub(crate) struct Subscriptions<W>(BTreeMap<String, Handler<W>>);
+
+impl<W> Subscriptions<W> {
+    pub(crate) fn new(subscriptions: Vec<Subscription<W>>) -> Self {
+        Self(subscriptions.into_iter()
+            .map(|subscription| (subscription.key, subscription.handler))
+            .collect())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+pub(crate) async fn receive<W: Worker + 'static>(
+    envelope: web::Json<EventEnvelope>,
+    worker: web::Data<W>,
+    subscriptions: web::Data<Subscriptions<W>>
+) -> HttpResponse
+{
+    let envelope = envelope.into_inner();
+
+    // Unsubscribed events are acknowledged, publishers don't know what is handled
+    let Some(handler) = subscriptions.0.get(&subscription_key(&envelope.source, &envelope.event)) else {
+        debug!("No subscription to event {} of {}", envelope.event, envelope.source);
+        return HttpResponse::Ok().finish()
+    };
+
+    match handler(worker.get_ref().clone(), envelope.payload).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ Error::Service(ServiceError::JSONParseError(_))) => {
+            warn!("Event {} of {} can't be parsed. {}", envelope.event, envelope.source, err);
+            HttpResponse::UnprocessableEntity().json(GateErrorResponse::from(err))
+        },
+        Err(err) => {
+            warn!("Event {} of {} failed. {}", envelope.event, envelope.source, err);
+            HttpResponse::InternalServerError().json(GateErrorResponse::from(err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use crate::http_events;
+    use super::*;
+
+    http_events!(TestEvents [
+        Created { id: u64 },
+        Cancelled { id: u64 }
+    ]);
+
+    // Subscriber failing with the error until the attempt
+    fn subscriber(attempts: Arc<AtomicU32>, fail_until: u32, err: fn() -> Error) -> Subscriber {
+        Subscriber {
+            name: "Test",
+            deliver: Arc::new(move |_, _| {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+                Box::pin(async move {
+                    match attempt < fail_until {
+                        true => Err(err()),
+                        false => Ok(())
+                    }
+                })
+            })
+        }
+    }
+
+    fn publisher(subscriber: Subscriber, attempts: u32) -> Publisher<TestEvents> {
+        let mut publisher = Publisher::new(reqwest::Client::new())
+            .set_retries(attempts, Duration::from_millis(1));
+        publisher.subscribers.push(subscriber);
+        publisher
+    }
+
+    fn overloaded() -> Error {
+        Error::Service(ServiceError::Overloaded)
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let retried = metrics::get("rmt.events.TestEvents.retried").unwrap_or(0);
+
+        publisher(subscriber(attempts.clone(), 3, overloaded), 5)
+            .publish(Created { id: 1 })
+            .await
+            .unwrap();
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(metrics::get("rmt.events.TestEvents.retried").unwrap_or(0) >= retried + 2);
+    }
+
+    #[tokio::test]
+    async fn dead_letter_after_retry_limit() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let dead_letters = metrics::get("rmt.events.TestEvents.dead_letters").unwrap_or(0);
+
+        let result = publisher(subscriber(attempts.clone(), u32::MAX, overloaded), 3)
+            .publish(Cancelled { id: 2 })
+            .await;
+
+        assert!(matches!(result, Err(Error::Service(ServiceError::Overloaded))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(metrics::get("rmt.events.TestEvents.dead_letters").unwrap_or(0) > dead_letters);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_are_not_retried() {
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let result = publisher(subscriber(attempts.clone(), u32::MAX, || Error::Service(ServiceError::Unauthorized)), 5)
+            .publish(Created { id: 3 })
+            .await;
+
+        assert!(matches!(result, Err(Error::Service(ServiceError::Unauthorized))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn routes_follow_mounts() {
+        assert_eq!(events_route(""), "/events");
+        assert_eq!(events_route("/billing/"), "/billing/events");
+    }
+}
@@ -0,0 +1,88 @@
+/** *http_events!*
+
+    Generates typed events of a publishing service
+    ```ignore
+    http_events! ( OrderEvents [
+        /// Order was placed
+        OrderCreated { id: u64, total: f64 },
+        OrderCancelled {
+            id: u64,
+            #[serde(default)]
+            reason: Option<String>
+        }
+    ])
+    ```
+
+    Generates ```OrderEvents``` set type and a struct per event.
+    Events are published with ```http::events::Publisher```
+    and handled by subscribers with ```http::events::Subscription```
+ */
+#[macro_export]
+macro_rules! http_events {
+    (
+        $(#[$set_attr:meta])*
+        $set_name:ident [
+            $(
+                $(#[$event_attr:meta])*
+                $event_name:ident { $(
+                    $(#[$field_attr:meta])*
+                    $field:ident : $ty:ty
+                ),* $(,)? }
+            ),* $(,)?
+        ]
+    ) => {
+        $(
+            #[derive($crate::serde::Serialize, $crate::serde::Deserialize, Clone, Debug)]
+            $(#[$event_attr])*
+            pub struct $event_name {
+                $( $(#[$field_attr])* pub $field : $ty ),*
+            }
+
+            impl $crate::http::events::Event for $event_name {
+                type Set = $set_name;
+                const NAME: &'static str = stringify!($event_name);
+            }
+        )*
+
+        $(#[$set_attr])*
+        pub struct $set_name { }
+
+        impl $crate::http::events::EventSet for $set_name {
+            const NAME: &'static str = stringify!($set_name);
+            const EVENTS: &'static [&'static str] = &[ $( stringify!($event_name) ),* ];
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::http::events::{Event, EventSet};
+
+    http_events!(
+        /// Events of tests
+        TestEvents [
+            /// Order was placed
+            Placed { id: u64 },
+            Withdrawn {
+                id: u64,
+                #[serde(default)]
+                reason: Option<String>
+            }
+        ]
+    );
+
+    #[test]
+    fn set_lists_its_events() {
+        assert_eq!(TestEvents::NAME, "TestEvents");
+        assert_eq!(TestEvents::EVENTS, ["Placed", "Withdrawn"]);
+        assert_eq!(<Withdrawn as Event>::NAME, "Withdrawn");
+    }
+
+    #[test]
+    fn events_keep_field_attributes() {
+        let event: Withdrawn = serde_json::from_value(json!({ "id": 4 })).unwrap();
+        assert_eq!((event.id, event.reason), (4, None));
+        assert_eq!(serde_json::to_value(Placed { id: 5 }).unwrap(), json!({ "id": 5 }));
+    }
+}
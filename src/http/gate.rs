@@ -1,24 +1,153 @@
-use crate::{Error, Payload, error::ServiceError, http::Worker};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
 
-// Gate which has request, response and a processor
-pub trait Gate: Payload {
+use crate::{Error, Payload, http::{RequestContext, Worker, concurrency::Priority, validation::FieldError}};
+
+/** Gate access.
+    ```Inherit``` follows the internal flag of the service context
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Access {
+    Inherit,
+    Public,
+    Internal
+}
+
+impl Access {
+    pub const fn is_internal(&self, context_internal: bool) -> bool {
+        match self {
+            Access::Inherit => context_internal,
+            Access::Public => false,
+            Access::Internal => true
+        }
+    }
+}
+
+/** **Gate Meta**
+
+    Gate description, generated by ```http_gates!```.
+    Options are set after gate request and response
+    ```ignore
+    Ping {
+        request: { },
+        response: { },
+        access: Internal,
+        max_in_flight: 4,
+        queue: 16,
+        priority: Critical,
+        cache_ttl: Duration::from_secs(30)
+    }
+    ```
+    ```max_in_flight``` 0 - unlimited, ```cache_ttl``` zero - not cached.
+    ```job: true``` runs the gate in background, the call returns a job to poll
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct GateMeta {
+    pub name: &'static str,
+    pub attributes: &'static [&'static str],
+    // Container attributes of all payloads of the service, e.g. ```serde(rename_all = "camelCase")```
+    pub container: &'static [&'static str],
+    pub request: &'static [FieldMeta],
+    pub response: &'static [FieldMeta],
+    pub access: Access,
+    pub max_in_flight: usize,
+    pub queue: usize,
+    pub priority: Priority,
+    pub cache_ttl: Duration,
+    pub job: bool
+}
+
+impl GateMeta {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attributes: &[],
+            container: &[],
+            request: &[],
+            response: &[],
+            access: Access::Inherit,
+            max_in_flight: 0,
+            queue: 0,
+            priority: Priority::Normal,
+            cache_ttl: Duration::ZERO,
+            job: false
+        }
+    }
+}
+
+/** Request or response field, as declared in ```http_gates!```.
+    Attributes include doc comments as ```doc = "..."```
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct FieldMeta {
+    pub name: &'static str,
+    pub ty: &'static str,
+    pub attributes: &'static [&'static str]
+}
+
+/** Names available for gate options inside of ```http_gates!``` */
+pub mod options {
+    pub use super::Access::*;
+    pub use crate::http::concurrency::Priority::*;
+    pub use std::time::Duration;
+}
+
+// Gate definition, generated for each request by ```http_gates!```
+pub trait GateDefinition: Payload {
+    type Service: Service;
     type Response: Payload;
+
+    const META: GateMeta;
+
+    /** Checks validation rules of request fields, returns every failure */
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+/** **Gate Handler**
+
+    Gate which has request, response and a processor.
+    Every gate of a service must have a handler for the worker to compile.
+    Implemented with ```#[rmtm::http_gate(MyService::GateName | Worker)]```,
+    a handler written without the context argument gets ```_context: &rmt::http::RequestContext```
+    added by the macro, so the crate must be reachable as ```rmt```
+ */
+#[diagnostic::on_unimplemented(
+    message = "gate request `{Self}` has no handler",
+    label = "no `#[rmtm::http_gate(...)]` for this gate",
+    note = "add `#[rmtm::http_gate(MyService::GateName | Worker)] async fn process(...)` for every gate of the service"
+)]
+pub trait Gate: GateDefinition {
     type W: Worker;
 
-    #[allow(unused_variables, async_fn_in_trait)]
-    async fn process(self, worker: &Self::W) -> Result<Self::Response, Error>
-    {
-        Err(Error::Service(ServiceError::NotImplemented))
-    }
+    #[allow(async_fn_in_trait)]
+    async fn process(self, worker: &Self::W, context: &RequestContext) -> Result<Self::Response, Error>;
+}
+
+/** Calls the gate handler, used by workers bound with ```http_bind_worker!```.
+    The handler must be implemented for the worker of the service
+ */
+pub async fn dispatch<W, G>(gate: G, worker: &W, context: &RequestContext) -> Result<G::Response, Error>
+where
+    W: Worker,
+    G: Gate<W = W> + GateDefinition<Service = W::S>,
+{
+    gate.process(worker, context).await
 }
 
 // Marker for requests enum
-pub trait RequestGatesMarker: Payload { }
+pub trait RequestGatesMarker: Payload {
+    fn meta(&self) -> GateMeta;
+    fn validate(&self) -> Vec<FieldError>;
+}
 // Marker for responses enum
 pub trait ResponseGatesMarker: Payload { }
 
 // Combined trait of requests and responses
-pub trait Service {
+pub trait Service: 'static {
     type Requests: RequestGatesMarker;
     type Responses: ResponseGatesMarker;
-}
\ No newline at end of file
+
+    const NAME: &'static str;
+    const VERSION: &'static str;
+    const GATES: &'static [GateMeta];
+}
@@ -2,30 +2,143 @@
 
     Generates gates
     ```
+    # use rmt::{http::Service, http_gates};
     http_gates! ( MyService [
         Ping {
-            request: { ... },
-            response: { ... }
+            request: { },
+            response: { }
         },
         Time {
-            request: { ... },
-            response: { ... }
+            request: { zone: String },
+            response: { time: u64 },
+            access: Internal
+        }
+    ]);
+    # fn main() {
+    assert_eq!(MyService::NAME, "MyService");
+    assert_eq!(MyService::GATES.len(), 2);
+    # }
+    ```
+
+    Generate Service type with provided name.
+    Service version is optional, services of one route are served by major version
+    ```ignore
+    http_gates! ( MyService version "1.2.0" [ ... ])
+    ```
+
+    Request fields may have validation rules, checked by Instance before processing.
+    Rules are functions of ```http::validation```, optional fields are checked when present
+    ```ignore
+    Register {
+        request: {
+            name: String [len(1, 64)],
+            email: String [regex(r"[^@]+@[^@]+")],
+            age: Option<u8> [range(18, 150)],
+            tags: Vec<String> [non_empty],
+            code: String [custom(check_code)]
+        },
+        response: { ... }
+    }
+    ```
+
+    Gate options follow the response, see ```http::GateMeta```
+
+    Attributes and doc comments are passed to generated structs.
+    Gate attributes are applied to both request and response.
+    ```rename_all``` is applied to all payloads of the service
+    ```ignore
+    http_gates! ( MyService version "1.2.0" rename_all "camelCase" [
+        /// Sends a message
+        Msg {
+            request: { 
+                /// Message text
+                msg: String [len(1, 256)],
+                #[serde(default)]
+                reply_to: Option<String>
+            },
+            response: { 
+                #[serde(skip_serializing_if = "Option::is_none")]
+                last_msg: Option<String> 
+            }
         }
     ])
     ```
 
-    Generate Service type with provided name
+    Generates ```MyServiceClient``` with an async method per gate
+    ```ignore
+    let client = MyServiceClient::new(&SERVICE_CONTEXT, reqwest::Client::new());
+    let response = client.msg("hello".to_string()).await?;
+    client.ping().await?;
+    ```
+    and ```MyServiceBlockingClient``` with ```reqwest::blocking::Client```.
+    Gates ```New``` and ```Context``` would collide with client methods and are rejected
 
     Generates additional auxiliary binding macro
  */
 #[macro_export]
 macro_rules! http_gates {
+    // Payload struct with service container attributes
     (
-        $service_name:ident [
+        @struct [ $(#[$container:meta])* ] $(#[$attr:meta])* pub struct $name:ident { $($body:tt)* }
+    ) => {
+        #[derive($crate::serde::Serialize, $crate::serde::Deserialize, Clone)]
+        $(#[$container])*
+        $(#[$attr])*
+        pub struct $name { $($body)* }
+        impl $crate::Payload for $name { }
+    };
+    (
+        @attributes [ $(#[$container:meta])* ]
+    ) => {
+        &[ $( stringify!($container) ),* ]
+    };
+    // Gates named as methods of generated clients are rejected before the service is generated
+    (
+        @check [ $(#[$gate_attr:meta])* New { $($gate:tt)* } $($rest:tt)* ] $($service:tt)*
+    ) => {
+        compile_error!("gate `New` collides with `new` method of generated clients");
+    };
+    (
+        @check [ $(#[$gate_attr:meta])* Context { $($gate:tt)* } $($rest:tt)* ] $($service:tt)*
+    ) => {
+        compile_error!("gate `Context` collides with `context` method of generated clients");
+    };
+    (
+        @check [ $(#[$gate_attr:meta])* $gate_name:ident { $($gate:tt)* } $(, $($rest:tt)*)? ] $($service:tt)*
+    ) => {
+        $crate::http_gates! { @check [ $($($rest)*)? ] $($service)* }
+    };
+    (
+        @check [ ] $($service:tt)*
+    ) => {
+        $crate::http_gates! { $($service)* }
+    };
+    (
+        $(#[$service_attr:meta])*
+        $service_name:ident $(version $version:literal)? $(rename_all $rename_all:literal)? [ $($gates:tt)* ]
+    ) => {
+        $crate::http_gates! {
+            @check [ $($gates)* ]
+            @service $(#[$service_attr])* $service_name [ $($version)? ] 
+            [ $( #[serde(rename_all = $rename_all)] )? ] 
+            [ $($gates)* ]
+        }
+    };
+    (
+        @service $(#[$service_attr:meta])* $service_name:ident [ $($version:literal)? ] $container:tt [
             $(
+                $(#[$gate_attr:meta])*
                 $gate_name:ident {
-                    request: { $($req_field:ident : $req_ty:ty),* $(,)? },
-                    response: { $($res_field:ident : $res_ty:ty),* $(,)? }
+                    request: { $(
+                        $(#[$req_attr:meta])*
+                        $req_field:ident : $req_ty:ty 
+                        $([ $( $rule:ident $(( $($rule_arg:expr),* ))? ),* $(,)? ])?
+                    ),* $(,)? },
+                    response: { $(
+                        $(#[$res_attr:meta])*
+                        $res_field:ident : $res_ty:ty
+                    ),* $(,)? }
+                    $(, $opt:ident : $opt_val:expr)* $(,)?
                 }
             ),* $(,)?
         ]
@@ -33,18 +146,60 @@ macro_rules! http_gates {
         $crate::paste::paste! {
             $(
                 // Gate request struct
-                #[derive($crate::serde::Serialize, $crate::serde::Deserialize, Clone)]
-                pub struct [<RMTHTTP $service_name $gate_name Req>] {
-                    $( pub $req_field : $req_ty ),*
+                $crate::http_gates! { 
+                    @struct $container
+                    $(#[$gate_attr])*
+                    pub struct [<RMTHTTP $service_name $gate_name Req>] {
+                        $( $(#[$req_attr])* pub $req_field : $req_ty ),*
+                    }
                 }
-                impl $crate::Payload for [<RMTHTTP $service_name $gate_name Req>] { }
 
                 // Gate response struct
-                #[derive($crate::serde::Serialize, $crate::serde::Deserialize, Clone)]
-                pub struct [<RMTHTTP $service_name $gate_name Res>] {
-                    $( pub $res_field : $res_ty ),*
+                $crate::http_gates! { 
+                    @struct $container
+                    $(#[$gate_attr])*
+                    pub struct [<RMTHTTP $service_name $gate_name Res>] {
+                        $( $(#[$res_attr])* pub $res_field : $res_ty ),*
+                    }
+                }
+
+                impl $crate::http::GateDefinition for [<RMTHTTP $service_name $gate_name Req>] {
+                    type Service = $service_name;
+                    type Response = [<RMTHTTP $service_name $gate_name Res>];
+
+                    const META: $crate::http::GateMeta = {
+                        #[allow(unused_imports)]
+                        use $crate::http::options::*;
+
+                        $crate::http::GateMeta { 
+                            $( $opt: $opt_val, )* 
+                            attributes: &[ $( stringify!($gate_attr) ),* ],
+                            container: $crate::http_gates!(@attributes $container),
+                            request: &[ $( $crate::http::FieldMeta { 
+                                name: stringify!($req_field), 
+                                ty: stringify!($req_ty),
+                                attributes: &[ $( stringify!($req_attr) ),* ]
+                            } ),* ],
+                            response: &[ $( $crate::http::FieldMeta { 
+                                name: stringify!($res_field), 
+                                ty: stringify!($res_ty),
+                                attributes: &[ $( stringify!($res_attr) ),* ]
+                            } ),* ],
+                            ..$crate::http::GateMeta::new(stringify!($gate_name)) 
+                        }
+                    };
+
+                    fn validate(&self) -> Vec<$crate::http::validation::FieldError> {
+                        #[allow(unused_mut)]
+                        let mut errors = Vec::new();
+                        $($($(
+                            if let Err(message) = $crate::http::validation::$rule(&self.$req_field $($(, $rule_arg)*)?) {
+                                errors.push($crate::http::validation::FieldError::of(&Self::META, stringify!($req_field), message));
+                            }
+                        )*)?)*
+                        errors
+                    }
                 }
-                impl $crate::Payload for [<RMTHTTP $service_name $gate_name Res>] { }
 
                 impl From<[<RMTHTTP $service_name $gate_name Res>]> for [<RMTHTTP $service_name ResGates>] {
                     fn from(item: [<RMTHTTP $service_name $gate_name Res>]) -> Self {
@@ -52,16 +207,16 @@ macro_rules! http_gates {
                     }
                 }
 
-                impl From<[<RMTHTTP $service_name ReqGates>]> for [<RMTHTTP $service_name $gate_name Req>] {
-                    fn from(value: [<RMTHTTP $service_name ReqGates>]) -> Self {
-                        let gate = if let [<RMTHTTP $service_name ReqGates>]::$gate_name(req) = value {
+                impl TryFrom<[<RMTHTTP $service_name ReqGates>]> for [<RMTHTTP $service_name $gate_name Req>] {
+                    type Error = $crate::Error;
+
+                    fn try_from(value: [<RMTHTTP $service_name ReqGates>]) -> Result<Self, Self::Error> {
+                        #[allow(irrefutable_let_patterns)]
+                        if let [<RMTHTTP $service_name ReqGates>]::$gate_name(req) = value {
                             Ok(req)
                         } else {
-                            Err(())
-                        };
-
-                        gate.map_err(|()| $crate::error!("Gate conversion failed"))
-                            .unwrap()
+                            Err($crate::Error::Service($crate::error::ServiceError::WrongGate))
+                        }
                     }
                 }
                 
@@ -81,7 +236,24 @@ macro_rules! http_gates {
                 ),*
             }
             impl $crate::Payload for [<RMTHTTP $service_name ReqGates>] { }
-            impl $crate::http::RequestGatesMarker for [<RMTHTTP $service_name ReqGates>] { }
+            impl $crate::http::RequestGatesMarker for [<RMTHTTP $service_name ReqGates>] { 
+                fn meta(&self) -> $crate::http::GateMeta {
+                    match self {
+                        $(
+                            Self::$gate_name(_) => 
+                                <[<RMTHTTP $service_name $gate_name Req>] as $crate::http::GateDefinition>::META
+                        ),*
+                    }
+                }
+
+                fn validate(&self) -> Vec<$crate::http::validation::FieldError> {
+                    match self {
+                        $(
+                            Self::$gate_name(req) => $crate::http::GateDefinition::validate(req)
+                        ),*
+                    }
+                }
+            }
 
 
             // Response gates enum
@@ -95,19 +267,89 @@ macro_rules! http_gates {
             impl $crate::Payload for [<RMTHTTP $service_name ResGates>] { }
             impl $crate::http::ResponseGatesMarker for [<RMTHTTP $service_name ResGates>] { }
 
+            $(#[$service_attr])*
             pub struct $service_name { }
             impl $crate::http::Service for $service_name {
                 type Requests = [<RMTHTTP $service_name ReqGates>];
                 type Responses = [<RMTHTTP $service_name ResGates>];
+
+                const NAME: &'static str = stringify!($service_name);
+                const VERSION: &'static str = {
+                    let version: &[&str] = &[$($version)?];
+                    if version.is_empty() { "0.0.0" } else { version[0] }
+                };
+                const GATES: &'static [$crate::http::GateMeta] = &[
+                    $( <[<RMTHTTP $service_name $gate_name Req>] as $crate::http::GateDefinition>::META ),*
+                ];
+            }
+
+            #[doc = concat!("Typed client of ```", stringify!($service_name), "```, one method per gate")]
+            #[derive(Clone)]
+            pub struct [<$service_name Client>] {
+                http_client: $crate::reqwest::Client,
+                context: &'static $crate::http::Context<$service_name>
+            }
+
+            impl [<$service_name Client>] {
+                pub fn new(context: &'static $crate::http::Context<$service_name>, http_client: $crate::reqwest::Client) -> Self {
+                    Self { http_client, context }
+                }
+
+                pub fn context(&self) -> &'static $crate::http::Context<$service_name> {
+                    self.context
+                }
+
+                $(
+                    #[doc = concat!("Calls ```", stringify!($gate_name), "``` gate")]
+                    #[allow(clippy::too_many_arguments)]
+                    pub async fn [<$gate_name:snake>](&self, $( $req_field : $req_ty ),*)
+                        -> Result<[<RMTHTTP $service_name $gate_name Res>], $crate::Error>
+                    {
+                        self.context.request(
+                            self.http_client.clone(),
+                            [<RMTHTTP $service_name $gate_name Req>] { $( $req_field ),* }
+                        ).await
+                    }
+                )*
+            }
+
+            #[doc = concat!("Blocking client of ```", stringify!($service_name), "```, for synchronous callers")]
+            #[derive(Clone)]
+            pub struct [<$service_name BlockingClient>] {
+                http_client: $crate::reqwest::blocking::Client,
+                context: &'static $crate::http::Context<$service_name>
+            }
+
+            impl [<$service_name BlockingClient>] {
+                pub fn new(context: &'static $crate::http::Context<$service_name>, http_client: $crate::reqwest::blocking::Client) -> Self {
+                    Self { http_client, context }
+                }
+
+                pub fn context(&self) -> &'static $crate::http::Context<$service_name> {
+                    self.context
+                }
+
+                $(
+                    #[doc = concat!("Calls ```", stringify!($gate_name), "``` gate")]
+                    #[allow(clippy::too_many_arguments)]
+                    pub fn [<$gate_name:snake>](&self, $( $req_field : $req_ty ),*)
+                        -> Result<[<RMTHTTP $service_name $gate_name Res>], $crate::Error>
+                    {
+                        self.context.request_blocking(
+                            &self.http_client,
+                            [<RMTHTTP $service_name $gate_name Req>] { $( $req_field ),* }
+                        )
+                    }
+                )*
             }
 
             #[macro_export]
             macro_rules! [<$service_name:snake _binder__>] {
-                [ $worker:ident | $request:ident ] => {
+                [ $worker:ident | $request:ident | $context:ident ] => {
                     match $request {
                         $(
                             [<RMTHTTP $service_name ReqGates>]::$gate_name(req) =>
-                                <[<RMTHTTP $service_name $gate_name Req>] as $crate::http::Gate>::process(req, $worker)
+                                $crate::http::gate::dispatch(req, $worker, $context)
                                     .await
                                     .map(|res| Into::<[<RMTHTTP $service_name ResGates>]>::into(res))
                         ),*
@@ -130,9 +372,9 @@ macro_rules! http_bind_worker {
                 &$context
             }
 
-            async fn matcher(&self, request: <Self::S as $crate::http::Service>::Requests)
+            async fn matcher(&self, request: <Self::S as $crate::http::Service>::Requests, context: &$crate::http::RequestContext)
                 -> Result<<Self::S as $crate::http::Service>::Responses, $crate::Error> 
-            { [<$service_name:snake _binder__>]![ self | request ] }
+            { [<$service_name:snake _binder__>]![ self | request | context ] }
         }
     };
 }
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::{collections::{HashMap, hash_map::RandomState}, hash::BuildHasher, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant, SystemTime}};
+use actix_web::{HttpResponse, body::{self, BoxBody}, dev::{self, ServiceResponse}, http::{StatusCode, header}, middleware, web};
+use log::{debug, warn};
+use tokio::sync::watch;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENCY_REPLAY_HEADER: &str = "idempotent-replayed";
+
+// Expired responses are cleaned up once in the interval
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/** Generates an unique idempotency key */
+pub fn idempotency_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let state = RandomState::new();
+
+    format!("{:016x}{:016x}", state.hash_one((count, SystemTime::now())), state.hash_one(count))
+}
+
+/** Marks a response with a gate error. Gate errors are answered with ```200``` but are not stored */
+pub(crate) struct GateFailed;
+
+#[derive(Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    content_type: Option<header::HeaderValue>,
+    body: web::Bytes
+}
+
+enum Entry {
+    // Receiver is closed when the first execution ends
+    Pending(watch::Receiver<()>),
+    Done { response: StoredResponse, expires: Instant }
+}
+
+struct StoreState {
+    entries: HashMap<String, Entry>,
+    cleaned: Instant
+}
+
+/** **Idempotency Store**
+
+    Remembers successful responses by idempotency key of the caller for the window.
+    Retried requests replay the stored response instead of processing,
+    concurrent duplicates wait for the first execution
+ */
+pub(crate) struct IdempotencyStore {
+    window: Duration,
+    state: Mutex<StoreState>
+}
+
+// Removes the pending entry if the first execution did not finish
+struct Execution<'a> {
+    store: &'a IdempotencyStore,
+    key: String,
+    _done: watch::Sender<()>,
+    finished: bool
+}
+
+enum Claim<'a> {
+    Execute(Execution<'a>),
+    Wait(watch::Receiver<()>),
+    Replay(StoredResponse)
+}
+
+impl IdempotencyStore {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::new(StoreState { entries: HashMap::new(), cleaned: Instant::now() })
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StoreState> {
+        self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn claim(&self, key: &str) -> Claim<'_> {
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        if now.duration_since(state.cleaned) >= CLEANUP_INTERVAL {
+            state.entries.retain(|_, entry| match entry {
+                Entry::Done { expires, .. } => *expires > now,
+                Entry::Pending(_) => true
+            });
+            state.cleaned = now;
+        }
+
+        match state.entries.get(key) {
+            Some(Entry::Done { response, expires }) if *expires > now => Claim::Replay(response.clone()),
+            Some(Entry::Pending(done)) => Claim::Wait(done.clone()),
+            _ => {
+                let (sender, receiver) = watch::channel(());
+                state.entries.insert(key.to_string(), Entry::Pending(receiver));
+
+                Claim::Execute(Execution { store: self, key: key.to_string(), _done: sender, finished: false })
+            }
+        }
+    }
+}
+
+impl Execution<'_> {
+    fn finish(mut self, response: Option<StoredResponse>) {
+        let mut state = self.store.lock();
+
+        match response {
+            Some(response) => {
+                let expires = Instant::now() + self.store.window;
+                state.entries.insert(self.key.clone(), Entry::Done { response, expires });
+            },
+            None => { state.entries.remove(&self.key); }
+        }
+
+        self.finished = true;
+    }
+}
+
+impl Drop for Execution<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.store.lock().entries.remove(&self.key);
+        }
+    }
+}
+
+fn replay(request: dev::ServiceRequest, response: StoredResponse) -> ServiceResponse<BoxBody> {
+    let mut builder = HttpResponse::build(response.status);
+    builder.insert_header((IDEMPOTENCY_REPLAY_HEADER, "true"));
+
+    if let Some(content_type) = response.content_type {
+        builder.insert_header((header::CONTENT_TYPE, content_type));
+    }
+
+    request.into_response(builder.body(response.body))
+}
+
+pub(crate) async fn idempotency_middleware(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+{
+    let Some(store) = request.app_data::<web::Data<IdempotencyStore>>().cloned() else {
+        return next.call(request).await
+    };
+
+    // Keys are scoped to the caller, so other callers can't replay its responses
+    let Some(key) = request.headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| format!("{}:{}:{}", request.connection_info().peer_addr().unwrap_or_default(), request.path(), value))
+    else {
+        return next.call(request).await
+    };
+
+    let execution = loop {
+        match store.claim(&key) {
+            Claim::Execute(execution) => break execution,
+            Claim::Replay(response) => {
+                debug!("Replaying response of {}", key);
+                return Ok(replay(request, response))
+            },
+            // Resolves when the first execution ends
+            Claim::Wait(mut done) => { let _ = done.changed().await; }
+        }
+    };
+
+    let response = next.call(request).await?;
+
+    // Only successful responses are results, rejections and gate errors can be retried
+    if !response.status().is_success() || response.response().extensions().get::<GateFailed>().is_some() {
+        execution.finish(None);
+        return Ok(response)
+    }
+
+    let (request, response) = response.into_parts();
+    let status = response.status();
+    let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+
+    let body = body::to_bytes(response.into_body()).await
+        .map_err(|err| {
+            warn!("Response body of {} can't be stored. {}", key, err);
+            actix_web::error::ErrorInternalServerError("Response body")
+        })?;
+
+    let stored = StoredResponse { status, content_type: content_type.clone(), body: body.clone() };
+    execution.finish(Some(stored));
+
+    let mut builder = HttpResponse::build(status);
+    if let Some(content_type) = content_type {
+        builder.insert_header((header::CONTENT_TYPE, content_type));
+    }
+
+    Ok(ServiceResponse::new(request, builder.body(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &'static str) -> StoredResponse {
+        StoredResponse { status: StatusCode::OK, content_type: None, body: web::Bytes::from(body) }
+    }
+
+    fn execute<'a>(store: &'a IdempotencyStore, key: &str) -> Execution<'a> {
+        match store.claim(key) {
+            Claim::Execute(execution) => execution,
+            _ => panic!("key {key} is already claimed")
+        }
+    }
+
+    #[test]
+    fn finished_execution_is_replayed() {
+        let store = IdempotencyStore::new(Duration::from_secs(60));
+        execute(&store, "a").finish(Some(response("first")));
+
+        match store.claim("a") {
+            Claim::Replay(stored) => assert_eq!(stored.body, "first"),
+            _ => panic!("response is not replayed")
+        }
+        execute(&store, "b");
+    }
+
+    #[tokio::test]
+    async fn duplicate_waits_for_pending_execution() {
+        let store = IdempotencyStore::new(Duration::from_secs(60));
+        let execution = execute(&store, "a");
+
+        let Claim::Wait(mut done) = store.claim("a") else { panic!("duplicate is not waiting") };
+        execution.finish(Some(response("first")));
+
+        assert!(done.changed().await.is_err());
+        assert!(matches!(store.claim("a"), Claim::Replay(_)));
+    }
+
+    #[test]
+    fn failed_or_dropped_execution_is_not_stored() {
+        let store = IdempotencyStore::new(Duration::from_secs(60));
+
+        execute(&store, "a").finish(None);
+        drop(execute(&store, "a"));
+
+        execute(&store, "a");
+    }
+
+    #[test]
+    fn stored_response_expires() {
+        let store = IdempotencyStore::new(Duration::from_millis(20));
+        execute(&store, "a").finish(Some(response("first")));
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        execute(&store, "a");
+    }
+}
@@ -1,61 +1,159 @@
-use std::sync::Arc;
-use actix_web::{App, HttpServer, body::BoxBody, dev::{self, ServiceResponse}, middleware::{self, from_fn}, web};
-use log::{error, info, trace, warn};
+use std::{collections::{BTreeMap, BTreeSet, HashSet}, future::Future, net::SocketAddr, path::PathBuf, pin::Pin, sync::Arc, time::Duration};
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, HttpServer, body::BoxBody, guard, dev::{self, ServiceResponse}, middleware::{self, from_fn}, web};
+use actix_web::rt::task::JoinHandle;
+use log::{debug, error, info, trace, warn};
 
-use crate::{Origin, common_gate::{GateErrorResponse, GateResult}, http::{Worker, gate::Service}, logger::init_logger, origin::AllowedOrigins};
+use crate::{Error, Origin, config::Config, metrics, logger::set_log_level, common_gate::{GateErrorResponse, GateResult}, error::ServiceError, http::{Concurrency, RateLimiter, RequestContext, RoutePolicy, Worker, describe::{self, BuildInfo, DESCRIBE_ROUTE, Description, ServiceDescription}, schedule::ScheduledTask, events::{self, Subscriptions, events_route}, jobs::{self, JOB_RETENTION, JobStore, jobs_route}, tls, concurrency::{ConcurrencyLimits, Permits, concurrency_middleware}, idempotency::{GateFailed, IdempotencyStore, idempotency_middleware}, gate::{RequestGatesMarker, Service}, limiter::limit_middleware, policy::RoutePolicies, version}, logger::init_logger, origin::AllowedOrigins};
 
+#[allow(clippy::large_enum_variant)]
 pub enum Encryption {
     TLS(rustls::ServerConfig),
     SSL(openssl::ssl::SslAcceptorBuilder),
+    // Watched by the instance and reloaded on changes
+    PEM(Arc<tls::ReloadableTls>),
     None
 }
 
+impl Encryption {
+    /** TLS from PEM certificate chain and private key files.
+        With CA, clients are required to present certificates signed by it
+     */
+    pub fn pem(cert: impl Into<PathBuf>, key: impl Into<PathBuf>, ca: Option<impl Into<PathBuf>>) -> std::io::Result<Self> {
+        Ok(Self::PEM(Arc::new(tls::ReloadableTls::load(cert, key, ca)?)))
+    }
+}
+
 /** **Service Instance**
 
-    creates an instance to run the worker.
+    creates an instance to run one or more workers.
+    Every mounted worker is served under its context route,
+    while encryption, allowed origins and workers count are shared.
+    Public and internal gates are served on separate routes, each with its own policy.
  */
-pub struct Instance<W: Worker> {
-    worker: W,
-    internal: bool,
+pub struct Instance {
+    origin: &'static Origin,
+    services: Vec<MountedService>,
     allowed_origins: Option<Vec<Origin>>,
+    policies: RoutePolicies,
+    rate_limiter: Option<RateLimiter>,
+    concurrency: ConcurrencyLimits,
+    idempotency: Option<Duration>,
+    describe: Option<BuildInfo>,
+    metrics: bool,
+    port: Option<u16>,   // overrides port of the main context
+    job_retention: Duration,
     encryption: Encryption,
     workers_count: usize   // 0 - automatic by actix
 }
 
-async fn processor<W: Worker>(data: web::Json<<W::S as Service>::Requests>, worker: web::Data<W>) 
-    -> web::Json<GateResult<<W::S as Service>::Responses>>  
+// Registers a mounted worker inside of an actix app
+type Mount = Arc<dyn Fn(&mut web::ServiceConfig, &PortMap) + Send + Sync>;
+// Context ports to the bound ones
+type PortMap = BTreeMap<u16, u16>;
+// Calls reload of a mounted worker
+type Reload = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), Error>>>> + Send + Sync>;
+
+struct MountedService {
+    version: &'static str,
+    description: ServiceDescription,
+    reload: Reload,
+    tasks: Vec<ScheduledTask>,
+    routes: Vec<String>,
+    ports: Vec<u16>,
+    mount: Mount
+}
+
+// Public or internal gates route of a mounted worker
+#[derive(Clone, Copy)]
+struct GateRoute {
+    mount: &'static str,
+    internal: bool,
+    port: u16
+}
+
+async fn processor<W: Worker + 'static>(
+    data: web::Json<<W::S as Service>::Requests>, 
+    worker: web::Data<W>, 
+    jobs: web::Data<JobStore>, 
+    request: HttpRequest,
+    route: GateRoute
+) -> HttpResponse
 {
     let data = data.into_inner();
+    let gate = data.meta();
+    // Taken out of the request, with values added by middleware
+    let context = request.extensions_mut()
+        .remove::<RequestContext>()
+        .unwrap_or_else(|| RequestContext::new(&request, route.internal));
+
+    let errors = data.validate();
+    if !errors.is_empty() {
+        debug!("Gate {} request is invalid. {} field(s) failed", gate.name, errors.len());
+        return HttpResponse::UnprocessableEntity()
+            .json(GateErrorResponse::from(Error::Service(ServiceError::Validation(errors))))
+    }
+
+    if gate.job {
+        // Concurrency slots are held by the job, not the accepting request
+        let permits = request.extensions_mut().remove::<Permits>();
+
+        let status = jobs.start(&gate, route.mount, route.internal, jobs::caller(&request), async move {
+            let _permits = permits;
+            context.trace(true).scope(worker.matcher(data, &context)).await
+                .map(|response| serde_json::to_value(response).unwrap_or_default())
+                .map_err(|err| {
+                    error!("Error occured in job of gate {}. {}", gate.name, err);
+                    serde_json::to_value(GateErrorResponse::from(err)).unwrap_or_default()
+                })
+        });
 
-    let response = worker 
-        .matcher(data)
+        return match status {
+            Ok(status) => HttpResponse::Accepted().json(status),
+            Err(err) => {
+                error!("Job of gate {} can't be started. {}", gate.name, err);
+                HttpResponse::InternalServerError().json(GateErrorResponse::from(err))
+            }
+        }
+    }
+
+    let response = context.trace(false)
+        .scope(worker.matcher(data, &context))
         .await;
 
+    let failed = response.is_err();
     if let Err(err) = &response {
-        error!("Error occured in instance processor. {}", err.to_string())
+        error!("Error occured in instance processor. {}", err)
     }
 
-    web::Json(
+    let mut response = HttpResponse::Ok().json(
         response
-            .map(|g| GateResult::Ok(g))
+            .map(GateResult::Ok)
             .unwrap_or_else(|err| GateResult::Err(GateErrorResponse::from(err)))
-    )
+    );
+
+    if failed {
+        response.extensions_mut().insert(GateFailed);
+    }
+    response
 }
 
-async fn middleware<W>(
-    request: dev::ServiceRequest, 
-    next: middleware::Next<BoxBody>,
-) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
-    where W: Worker + 'static
-{
-    let origin = request.connection_info()
+fn peer_addr(request: &dev::ServiceRequest) -> Result<String, actix_web::Error> {
+    request.connection_info()
         .peer_addr()
         .ok_or_else( || {
             warn!("No peer address in middleware");
             actix_web::error::ErrorInternalServerError("No peer adress")
-        })?
-        .to_string();
+        })
+        .map(|addr| addr.to_string())
+}
 
+// Shared by all mounted services
+async fn origin_middleware(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+{
+    let origin = peer_addr(&request)?;
 
     trace!("New request from origin {}.", origin);
 
@@ -66,14 +164,130 @@ async fn middleware<W>(
         })?;
 
 
-    if let Some(origins) = allowed_origins.as_deref() { 
+    if let Some(origins) = allowed_origins.as_deref()
+        && !origins.contains(&origin)
+    {
+        info!("New request from unlisted origin {}!", origin);
+        return Ok(request.into_response(dev::Response::bad_request()));
+    };
+
+    next.call(request).await
+}
+
+// Checks port and policy of public or internal route
+async fn route_middleware(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+{
+    let route = *request.app_data::<GateRoute>()
+        .ok_or_else(|| {
+            warn!("No gate route in middleware");
+            actix_web::error::ErrorInternalServerError("Data error")
+        })?;
+
+    if request.app_config().local_addr().port() != route.port {
+        return Ok(request.into_response(dev::Response::not_found()));
+    }
+
+    let policies = request.app_data::<web::Data<RoutePolicies>>()
+        .ok_or_else(|| {
+            warn!("No route policies in middleware");
+            actix_web::error::ErrorInternalServerError("Data error")
+        })?
+        .clone();
+    let policy = policies.get(route.internal);
+
+    if let Some(origins) = &policy.allowed_origins {
+        let origin = peer_addr(&request)?;
+
         if !origins.contains(&origin) {
-            info!("New request from unlisted origin {}!", origin);
+            info!("New request from origin {} unlisted for the route!", origin);
             return Ok(request.into_response(dev::Response::bad_request()));
         }
-    };
+    }
+
+    if let Some(auth) = &policy.auth
+        && !auth(&request)
+    {
+        info!("Unauthorized request to {}", request.path());
+        return Ok(request.into_response(
+            HttpResponse::Unauthorized()
+                .json(GateErrorResponse::from(Error::Service(ServiceError::Unauthorized)))
+        ));
+    }
+
+    next.call(request).await
+}
+
+const METRICS_ROUTE: &str = "/metrics";
+
+async fn metrics_endpoint() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
+}
+
+// Same as actix json limit
+const GATE_PAYLOAD_LIMIT: usize = 2_097_152;
+
+#[derive(serde::Deserialize)]
+struct GateTag {
+    gate: String
+}
+
+// Resolves requested gate before the request is parsed, when limits or route access need it.
+// Gates of the other route are rejected before limits and worker hooks.
+// Gate meta and request context are stored in request extensions
+async fn gate_middleware<W>(
+    mut request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+    where W: Worker + 'static
+{
+    let internal = request.app_data::<GateRoute>().is_some_and(|route| route.internal);
+    let context = request.app_data::<web::Data<W>>().map(|worker| worker.context_ref());
+
+    // Routes of services with gates of one access serve only those
+    let mixed_access = context.is_some_and(|context| W::S::GATES.iter().any(|gate| context.is_internal(gate) != internal));
+    let limits_gates = request.app_data::<web::Data<RateLimiter>>().is_some_and(|limiter| limiter.limits_gates())
+        || request.app_data::<web::Data<ConcurrencyLimits>>().is_some_and(|limits| limits.limits_gates(W::S::GATES));
+
+    if mixed_access || limits_gates {
+        let body = request.extract::<web::Bytes>().await?;
+
+        let gate = serde_json::from_slice::<GateTag>(&body)
+            .ok()
+            .and_then(|tag| W::S::GATES.iter().find(|gate| gate.name == tag.gate));
+
+        if let Some(gate) = gate {
+            if context.is_some_and(|context| context.is_internal(gate) != internal) {
+                warn!("Gate {} was requested on a wrong route", gate.name);
+                return Ok(request.into_response(
+                    HttpResponse::Forbidden()
+                        .json(GateErrorResponse::from(Error::Service(ServiceError::WrongRoute)))
+                ))
+            }
+
+            request.extensions_mut().insert(*gate);
+        }
+
+        request.set_payload(dev::Payload::from(body));
+    }
+
+    let context = RequestContext::new(request.request(), internal);
+    request.extensions_mut().insert(context);
 
+    next.call(request).await
+}
 
+// Specific to the mounted worker
+async fn worker_middleware<W>(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+    where W: Worker + 'static
+{
     let worker = request.app_data::<web::Data<W>>()
         .ok_or_else(|| {
             warn!("No worker in middleware");
@@ -81,27 +295,27 @@ async fn middleware<W>(
         })?
         .clone();
 
-    let request = 
+    let request =
     if let Ok(request) = worker.get_ref()
         .middleware_pre(request)
         .await
         .map_err(|err| {
-            warn!("Worker pre middleware errored! {}", err.to_string());
-        }) { request } 
+            warn!("Worker pre middleware errored! {}", err);
+        }) { request }
     else {
         return Err(actix_web::error::ErrorInternalServerError("Worker pre middleware"))
     };
 
- 
+
     let response = next.call(request).await?;
 
-    let response = 
+    let response =
     if let Ok(response) = worker.get_ref()
         .middleware_post(response)
         .await
         .map_err(|err| {
-            warn!("Worker post middleware errored! {}", err.to_string());
-        }) { response } 
+            warn!("Worker post middleware errored! {}", err);
+        }) { response }
     else {
         return Err(actix_web::error::ErrorInternalServerError("Worker post middleware"))
     };
@@ -110,20 +324,162 @@ async fn middleware<W>(
 }
 
 
-impl<W> Instance<W> 
-    where W: Worker + 'static
-{
-    pub fn new(worker: W) -> Self {
+// Reloads mounted workers one after another, failed ones keep their old config
+async fn reload_workers(reloads: &[(String, Reload)]) {
+    for (name, reload) in reloads {
+        match reload().await {
+            Ok(()) => info!("Worker of {} reloaded", name),
+            Err(err) => error!("Worker of {} can't be reloaded, old config is kept. {}", name, err)
+        }
+    }
+}
+
+// Reloads mounted workers on SIGHUP, until the returned task is cancelled
+#[cfg(unix)]
+fn reload_on_hangup(reloads: Vec<(String, Reload)>) -> std::io::Result<Option<JoinHandle<()>>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    // Registered before the server starts, SIGHUP terminates the process otherwise
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    Ok(Some(actix_web::rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading workers");
+            reload_workers(&reloads).await;
+        }
+    })))
+}
+
+#[cfg(not(unix))]
+fn reload_on_hangup(_reloads: Vec<(String, Reload)>) -> std::io::Result<Option<JoinHandle<()>>> {
+    Ok(None)
+}
+
+fn reload_of<W: Worker + 'static>(worker: web::Data<W>) -> Reload {
+    Arc::new(move || {
+        let worker = worker.clone();
+        Box::pin(async move { worker.reload().await })
+    })
+}
+
+impl Instance {
+    /** Creates an instance from the main worker. Its context origin is used to bind the server */
+    pub fn new<W: Worker + 'static>(worker: W) -> Self {
         init_logger();
-        let internal = worker.context_ref().internal;
 
         Self {
-            internal,
-            worker,
+            origin: worker.context_ref().origin(),
+            services: Vec::new(),
             allowed_origins: None,
+            policies: RoutePolicies::default(),
+            rate_limiter: None,
+            concurrency: ConcurrencyLimits::default(),
+            idempotency: None,
+            describe: None,
+            metrics: false,
+            port: None,
+            job_retention: JOB_RETENTION,
             encryption: Encryption::None,
             workers_count: 0
+        }.mount(worker)
+    }
+
+    /** Mounts an additional worker.
+        Its context should be mounted under an unique path with ```Context::mount```.
+        Services with different major versions can share the path during migrations,
+        requests are dispatched by the version header.
+    */
+    pub fn mount<W: Worker + 'static>(mut self, worker: W) -> Self {
+        let context = worker.context_ref();
+
+        if context.origin().port() != self.origin.port() {
+            warn!("Service on route {} expects port {}, but instance binds to {}",
+                context.mount_path(), context.origin().port(), self.origin.port());
         }
+
+        // Only routes with at least one gate are served
+        let routes: Vec<(String, GateRoute)> = [false, true].into_iter()
+            .filter(|internal| W::S::GATES.iter().any(|gate| context.is_internal(gate) == *internal))
+            .map(|internal| (context.route(internal), GateRoute { mount: context.mount_path(), internal, port: context.port(internal) }))
+            .collect();
+
+        // Status routes of job gates
+        let job_routes: BTreeSet<bool> = W::S::GATES.iter()
+            .filter(|gate| gate.job)
+            .map(|gate| context.is_internal(gate))
+            .collect();
+
+        // Events are served with internal gates, behind the same middleware
+        let subscriptions = Subscriptions::new(worker.subscriptions());
+        let events = (!subscriptions.is_empty())
+            .then(|| (events_route(context.mount_path()), GateRoute { mount: context.mount_path(), internal: true, port: context.port(true) }, web::Data::new(subscriptions)));
+
+        let worker_state = web::Data::new(worker);
+        let version = W::S::VERSION;
+
+        self.services.push(MountedService {
+            version,
+            description: ServiceDescription::of(context),
+            reload: reload_of(worker_state.clone()),
+            tasks: worker_state.tasks().into_iter()
+                .map(|task| ScheduledTask::new(task, worker_state.get_ref().clone()))
+                .collect(),
+            routes: routes.iter().map(|(path, _)| path.clone())
+                .chain(events.iter().map(|(path, _, _)| path.clone()))
+                .collect(),
+            ports: routes.iter().map(|(_, route)| route.port)
+                .chain(events.iter().map(|(_, route, _)| route.port))
+                .collect(),
+            mount: Arc::new(move |config: &mut web::ServiceConfig, ports: &PortMap| {
+                for (path, route) in routes.iter().cloned() {
+                    let route = GateRoute { port: ports.get(&route.port).copied().unwrap_or(route.port), ..route };
+                    config.service(
+                        web::resource(path)
+                            .guard(guard::fn_guard(move |context| version::accepts(context, version)))
+                            .app_data(worker_state.clone())
+                            .app_data(route)
+                            .app_data(web::PayloadConfig::new(GATE_PAYLOAD_LIMIT))
+                            .wrap(from_fn(worker_middleware::<W>))
+                            .wrap(from_fn(concurrency_middleware))
+                            .wrap(from_fn(idempotency_middleware))
+                            .wrap(from_fn(limit_middleware))
+                            .wrap(from_fn(gate_middleware::<W>))
+                            .wrap(from_fn(route_middleware))
+                            .route(web::post().to(move |data, worker, jobs, request| processor::<W>(data, worker, jobs, request, route)))
+                    );
+
+                    if job_routes.contains(&route.internal) {
+                        config.service(
+                            web::resource(format!("{}/{{id}}", jobs_route(route.mount, route.internal)))
+                                .app_data(route)
+                                .wrap(from_fn(route_middleware))
+                                .route(web::get().to(move |id, jobs, request| jobs::status(id, jobs, request, route.mount, route.internal)))
+                                .route(web::delete().to(move |id, jobs, request| jobs::cancel(id, jobs, request, route.mount, route.internal)))
+                        );
+                    }
+                }
+
+                if let Some((path, route, subscriptions)) = &events {
+                    let route = GateRoute { port: ports.get(&route.port).copied().unwrap_or(route.port), ..*route };
+                    config.service(
+                        web::resource(path.clone())
+                            .guard(guard::fn_guard(move |context| version::accepts(context, version)))
+                            .app_data(worker_state.clone())
+                            .app_data(subscriptions.clone())
+                            .app_data(route)
+                            .app_data(web::JsonConfig::default().limit(GATE_PAYLOAD_LIMIT))
+                            .wrap(from_fn(worker_middleware::<W>))
+                            .wrap(from_fn(concurrency_middleware))
+                            .wrap(from_fn(idempotency_middleware))
+                            .wrap(from_fn(limit_middleware))
+                            .wrap(from_fn(gate_middleware::<W>))
+                            .wrap(from_fn(route_middleware))
+                            .route(web::post().to(events::receive::<W>))
+                    );
+                }
+            })
+        });
+        self
     }
 
     pub fn set_encryption(mut self, enc: Encryption) -> Self {
@@ -136,6 +492,49 @@ impl<W> Instance<W>
         self
     }
 
+    /** Binds to another port than the main context origin.
+        Routes of contexts on the origin port are moved to it
+     */
+    pub fn set_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /** Applies loaded config. Settings missing in config are kept */
+    pub fn set_config(mut self, config: &Config) -> std::io::Result<Self> {
+        if let Some(level) = config.log_level {
+            set_log_level(level);
+        }
+
+        if let Some(port) = config.port {
+            self = self.set_port(port);
+        }
+
+        if let Some(workers) = config.workers {
+            self = self.set_workers_count(workers);
+        }
+
+        if let Some(origins) = &config.allowed_origins {
+            // Origins are static, as the ones set in code. Config is loaded once
+            self = self.set_allowed_origins(origins.iter()
+                .map(|origin| Origin::IP { addr: Box::leak(origin.clone().into_boxed_str()), port: 0 })
+                .collect());
+        }
+
+        match (&config.tls_cert, &config.tls_key) {
+            (Some(cert), Some(key)) => {
+                self = self.set_encryption(Encryption::pem(cert, key, config.tls_ca.as_ref())?);
+            },
+            (None, None) if config.tls_ca.is_none() => (),
+            _ => return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput, 
+                "Both tls_cert and tls_key are required"
+            ))
+        }
+
+        Ok(self)
+    }
+
     /** Makes instance internal. Only requests from allowed origins are accepted.
         If allowed origins is ```None```, request from any origin will be accepted
     */
@@ -144,31 +543,203 @@ impl<W> Instance<W>
         self
     }
 
-    pub async fn run(self) -> std::io::Result<()> {
-        let origin = self.worker.context_ref().origin();
-        let ip = origin.self_host();
-        let port = origin.port();
+    /** Policy of public gates route */
+    pub fn set_public_policy(mut self, policy: RoutePolicy) -> Self {
+        self.policies.public = policy;
+        self
+    }
 
-        let worker_state = web::Data::new(self.worker.clone());
+    /** Policy of internal gates route */
+    pub fn set_internal_policy(mut self, policy: RoutePolicy) -> Self {
+        self.policies.internal = policy;
+        self
+    }
 
-        let route_path = match self.internal {
-            true => "/internal-request",
-            false => "/request"
-        };
+    /** Rate limiter shared by all mounted services */
+    pub fn set_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /** Concurrency limit shared by all gates. 
+        When it is reached, lower priority gates are shed first
+     */
+    pub fn set_concurrency(mut self, limit: Concurrency) -> Self {
+        self.concurrency.set_shared(limit);
+        self
+    }
+
+    /** Overrides concurrency limit from gate options */
+    pub fn set_gate_concurrency(mut self, gate: &str, limit: Concurrency) -> Self {
+        self.concurrency.set_gate(gate, limit);
+        self
+    }
+
+    /** Remembers responses by idempotency key for the window.
+        Requests with the same key replay the response instead of processing
+     */
+    pub fn set_idempotency(mut self, window: Duration) -> Self {
+        self.idempotency = Some(window);
+        self
+    }
+
+    /** Keeps results of finished jobs for polling. Default 1 hour */
+    pub fn set_job_retention(mut self, retention: Duration) -> Self {
+        self.job_retention = retention;
+        self
+    }
+
+    /** Serves the description of mounted services and their gates on ```/describe```.
+        Internal gates are listed as well, so it is served on the internal port of the main service
+        with the internal route policy
+     */
+    pub fn set_describe(mut self, build: BuildInfo) -> Self {
+        self.describe = Some(build);
+        self
+    }
+
+    /** Serves all metrics in Prometheus text format on ```/metrics```,
+        with the internal route of the main service and its policy
+     */
+    pub fn set_metrics(mut self, enabled: bool) -> Self {
+        self.metrics = enabled;
+        self
+    }
+
+    pub async fn run(mut self) -> std::io::Result<()> {
+        let ip = self.origin.self_host();
+
+        // Schedules are checked before the server binds
+        let tasks = self.services.iter_mut()
+            .flat_map(|service| std::mem::take(&mut service.tasks))
+            .map(|task| task.schedule().map(|schedule| (task, schedule)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let mut routes = HashSet::new();
+        if let Some((route, _)) = self.services.iter()
+            .flat_map(|service| service.routes.iter().map(|route| (route, version::compatibility(service.version))))
+            .find(|route| !routes.insert(*route))
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Route {} is used by several services of compatible versions", route)
+            ))
+        }
+
+        // Versions served on each route
+        let mut versions: BTreeMap<String, Vec<&'static str>> = BTreeMap::new();
+        for service in &self.services {
+            for route in &service.routes {
+                versions.entry(route.clone()).or_default().push(service.version);
+            }
+        }
+
+        let port_map: PortMap = self.port.iter()
+            .map(|port| (self.origin.port(), *port))
+            .collect();
+
+        let ports: BTreeSet<u16> = self.services.iter()
+            .flat_map(|service| service.ports.iter().copied())
+            .chain([self.origin.port()])
+            .map(|port| port_map.get(&port).copied().unwrap_or(port))
+            .collect();
+
+        let addrs: Vec<SocketAddr> = ports.iter()
+            .map(|port| format!("{ip}:{port}").parse())
+            .collect::<Result<_, _>>()
+            .map_err(std::io::Error::other)?;
+
+        let mounts: Vec<Mount> = self.services.iter()
+            .map(|service| {
+                info!("Mounting service of version {} on routes {}", service.version, service.routes.join(", "));
+                service.mount.clone()
+            })
+            .collect();
+
+        let hangup = reload_on_hangup(self.services.iter()
+            .map(|service| (service.description.name.clone(), service.reload.clone()))
+            .collect())?;
 
         let allowed_origins = web::Data::new(
             self.allowed_origins.as_ref()
             .map(|o| Arc::new(AllowedOrigins::from(o)))
         );
+        let policies = web::Data::new(self.policies);
+        let jobs = web::Data::new(JobStore::new(self.job_retention));
+        let rate_limiter = self.rate_limiter.map(web::Data::new);
+        let concurrency = web::Data::new(self.concurrency);
+        let idempotency = self.idempotency.map(|window| web::Data::new(IdempotencyStore::new(window)));
+        let description = self.describe.map(|build| web::Data::new(Description {
+            build,
+            services: self.services.iter()
+                .map(|service| ServiceDescription {
+                    port: port_map.get(&service.description.port).copied().unwrap_or(service.description.port),
+                    internal_port: service.description.internal_port
+                        .map(|port| port_map.get(&port).copied().unwrap_or(port)),
+                    ..service.description.clone()
+                })
+                .collect()
+        }));
+
+        // Instance endpoints are served with the internal route of the main service
+        let main = &self.services[0].description;
+        let internal_port = main.internal_port.unwrap_or(main.port);
+        let instance_route = GateRoute {
+            mount: "",
+            internal: true,
+            port: port_map.get(&internal_port).copied().unwrap_or(internal_port)
+        };
+        let serve_metrics = self.metrics;
 
-        info!("Starting http server on {}:{}", ip, port);
+        info!("Starting http server on {} with ports {:?}", ip, ports);
 
         let server = HttpServer::new(move || {
-            App::new()
-                .app_data(worker_state.clone())
+            let app = App::new()
+                .app_data(jobs.clone())
                 .app_data(allowed_origins.clone())
-                .wrap(from_fn(middleware::<W>))
-                .route(route_path, web::post().to(processor::<W>))
+                .app_data(policies.clone())
+                .app_data(concurrency.clone())
+                .wrap(from_fn(origin_middleware));
+
+            let app = match &rate_limiter {
+                Some(limiter) => app.app_data(limiter.clone()),
+                None => app
+            };
+
+            let app = match &idempotency {
+                Some(store) => app.app_data(store.clone()),
+                None => app
+            };
+
+            let app = match &description {
+                Some(description) => app.service(
+                    web::resource(DESCRIBE_ROUTE)
+                        .app_data(description.clone())
+                        .app_data(instance_route)
+                        .wrap(from_fn(route_middleware))
+                        .route(web::get().to(describe::describe))
+                ),
+                None => app
+            };
+
+            let app = match serve_metrics {
+                true => app.service(
+                    web::resource(METRICS_ROUTE)
+                        .app_data(instance_route)
+                        .wrap(from_fn(route_middleware))
+                        .route(web::get().to(metrics_endpoint))
+                ),
+                false => app
+            };
+
+            let app = mounts.iter()
+                .fold(app, |app, mount| app.configure(|config| mount(config, &port_map)));
+
+            versions.iter()
+                .fold(app, |app, (route, served)| {
+                    let served = served.clone();
+                    app.route(route, web::post().to(move |request| version::incompatible(request, served.clone())))
+                })
         });
 
         let server = if self.workers_count != 0 {
@@ -177,10 +748,205 @@ impl<W> Instance<W>
             server
         };
 
-        match self.encryption {
-            Encryption::None => server.bind((ip, port)),
-            Encryption::SSL(ssl) => server.bind_openssl((ip, port), ssl),
-            Encryption::TLS(tls) => server.bind_rustls_0_23((ip, port), tls)
-        }?.run().await
+        let (server, watch) = match self.encryption {
+            Encryption::None => (server.bind(&addrs[..])?, None),
+            Encryption::SSL(ssl) => (server.bind_openssl(&addrs[..], ssl)?, None),
+            Encryption::TLS(tls) => (server.bind_rustls_0_23(&addrs[..], tls)?, None),
+            Encryption::PEM(tls) => {
+                let server = server.bind_rustls_0_23(&addrs[..], tls.server_config()?)?;
+                (server, Some(actix_web::rt::spawn(tls.watch())))
+            }
+        };
+
+        let tasks: Vec<_> = tasks.into_iter()
+            .map(|(task, schedule)| actix_web::rt::spawn(task.run(schedule)))
+            .collect();
+
+        if !tasks.is_empty() {
+            info!("{} scheduled tasks are cancelled when the server stops", tasks.len());
+        }
+
+        serve(server.run(), tasks.into_iter().chain(watch).chain(hangup).collect()).await
+    }
+}
+
+// Runs the server, background tasks are cancelled once it stops
+pub(super) async fn serve(server: dev::Server, background: Vec<JoinHandle<()>>) -> std::io::Result<()> {
+    let result = server.await;
+    background.iter().for_each(|task| task.abort());
+    result
+}
+
+// Generated clients of the test service are not used
+#[cfg(test)]
+#[allow(dead_code)]
+mod tests {
+    use std::sync::Mutex;
+    use actix_web::test::{self, TestRequest};
+    use serde_json::{Value, json};
+
+    use crate::{http::{Context, Gate, Reloadable, request::{DEADLINE_HEADER, TRACE_ID_HEADER, Trace}}, http_bind_worker, http_context, http_gates};
+    use super::*;
+
+    http_gates!(TestService [
+        Whoami {
+            request: { },
+            response: { caller: String, trace_id: String, traced: bool }
+        },
+        Flush {
+            request: { },
+            response: { },
+            access: Internal
+        }
+    ]);
+
+    static CONTEXT: Context<TestService> = http_context![ ::2020 ];
+
+    // Hooks and handlers run for the caller header
+    static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn called(call: &str) -> bool {
+        CALLS.lock().unwrap().iter().any(|recorded| recorded == call)
+    }
+
+    struct Caller(String);
+
+    // Settings loaded on reload, missing ones fail it
+    static SETTINGS: Mutex<Option<String>> = Mutex::new(None);
+
+    #[derive(Clone, Default)]
+    pub struct TestWorker {
+        settings: Reloadable<String>
+    }
+
+    impl Worker for TestWorker {
+        http_bind_worker!{ CONTEXT | TestService }
+
+        async fn reload(&self) -> Result<(), Error> {
+            let settings = SETTINGS.lock().unwrap().clone()
+                .ok_or(Error::Custom(Some("Settings are missing".to_string())))?;
+
+            self.settings.set(settings);
+            Ok(())
+        }
+
+        async fn middleware_pre(&self, request: dev::ServiceRequest) -> Result<dev::ServiceRequest, Error> {
+            let caller = request.headers().get("caller")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+
+            CALLS.lock().unwrap().push(format!("pre:{caller}"));
+            RequestContext::extend(&request, Caller(caller));
+            Ok(request)
+        }
+    }
+
+    fn caller(context: &RequestContext) -> String {
+        context.get::<Caller>().map(|caller| caller.0.clone()).unwrap_or_default()
+    }
+
+    impl Gate for RMTHTTPTestServiceWhoamiReq {
+        type W = TestWorker;
+
+        async fn process(self, _worker: &Self::W, context: &RequestContext) -> Result<Self::Response, Error> {
+            CALLS.lock().unwrap().push(format!("process:{}", caller(context)));
+
+            Ok(Self::Response {
+                caller: caller(context),
+                trace_id: context.trace_id.clone(),
+                traced: Trace::current().is_some_and(|trace| trace.trace_id == context.trace_id && trace.deadline.is_some())
+            })
+        }
+    }
+
+    impl Gate for RMTHTTPTestServiceFlushReq {
+        type W = TestWorker;
+
+        async fn process(self, _worker: &Self::W, context: &RequestContext) -> Result<Self::Response, Error> {
+            CALLS.lock().unwrap().push(format!("process:{}", caller(context)));
+            Ok(Self::Response { })
+        }
+    }
+
+    // Gate route of the test worker, with the middleware of mounted services
+    fn gates(config: &mut web::ServiceConfig, path: &str, internal: bool) {
+        let route = GateRoute { mount: "", internal, port: 2020 };
+
+        config.service(web::resource(path)
+            .app_data(web::Data::new(TestWorker::default()))
+            .app_data(route)
+            .wrap(from_fn(worker_middleware::<TestWorker>))
+            .wrap(from_fn(concurrency_middleware))
+            .wrap(from_fn(gate_middleware::<TestWorker>))
+            .route(web::post().to(move |data, worker, jobs, request| processor::<TestWorker>(data, worker, jobs, request, route))));
+    }
+
+    fn gate_request(path: &str, caller: &str, gate: &str) -> TestRequest {
+        TestRequest::post()
+            .uri(path)
+            .insert_header(("caller", caller))
+            .set_json(json!({ "gate": gate }))
+    }
+
+    #[actix_web::test]
+    async fn middleware_values_reach_process() {
+        let app = test::init_service(App::new()
+            .app_data(web::Data::new(JobStore::new(JOB_RETENTION)))
+            .configure(|config| gates(config, "/request", false))
+        ).await;
+
+        let request = TestRequest::post()
+            .uri("/request")
+            .insert_header(("caller", "alice"))
+            .insert_header((TRACE_ID_HEADER, "trace-3"))
+            .insert_header((DEADLINE_HEADER, "5000"))
+            .set_json(json!({ "gate": "Whoami" }))
+            .to_request();
+
+        let response: Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response, json!({ "gate": "Whoami", "caller": "alice", "trace_id": "trace-3", "traced": true }));
+    }
+
+    #[actix_web::test]
+    async fn gate_of_other_route_is_rejected_before_worker_hooks() {
+        let app = test::init_service(App::new()
+            .app_data(web::Data::new(JobStore::new(JOB_RETENTION)))
+            .configure(|config| gates(config, "/request", false))
+            .configure(|config| gates(config, "/internal-request", true))
+        ).await;
+
+        let response = test::call_service(&app, gate_request("/request", "mallory", "Flush").to_request()).await;
+        assert_eq!(response.status(), 403);
+        assert!(!called("pre:mallory") && !called("process:mallory"));
+
+        let response = test::call_service(&app, gate_request("/internal-request", "mallory", "Whoami").to_request()).await;
+        assert_eq!(response.status(), 403);
+        assert!(!called("pre:mallory") && !called("process:mallory"));
+
+        let response = test::call_service(&app, gate_request("/internal-request", "carol", "Flush").to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert!(called("pre:carol") && called("process:carol"));
     }
-}
\ No newline at end of file
+
+    #[actix_web::test]
+    async fn reload_swaps_settings_and_failed_reload_keeps_them() {
+        let worker = web::Data::new(TestWorker::default());
+        let reloads = vec![("TestService".to_string(), reload_of(worker.clone()))];
+
+        *SETTINGS.lock().unwrap() = Some("first".to_string());
+        reload_workers(&reloads).await;
+        let first = worker.settings.get();
+        assert_eq!(*first, "first");
+
+        *SETTINGS.lock().unwrap() = Some("second".to_string());
+        reload_workers(&reloads).await;
+        assert_eq!(*worker.settings.get(), "second");
+        // Snapshots taken before the reload are kept
+        assert_eq!(*first, "first");
+
+        *SETTINGS.lock().unwrap() = None;
+        reload_workers(&reloads).await;
+        assert_eq!(*worker.settings.get(), "second");
+    }
+}
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, future::Future, sync::{Arc, Mutex, MutexGuard}, time::{Duration, Instant}};
+use actix_web::{HttpRequest, HttpResponse, web};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tokio::task::AbortHandle;
+
+use crate::{Error, common_gate::GateErrorResponse, error::ServiceError, http::{GateMeta, context::route}};
+
+/** Finished jobs are kept for polling this long, unless set by ```Instance::set_job_retention``` */
+pub const JOB_RETENTION: Duration = Duration::from_secs(3600);
+
+// Finished jobs are cleaned up once in the interval
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/** Route of jobs of public or internal gates, ```/<route>/jobs/<id>``` */
+pub fn jobs_route(mount: &str, internal: bool) -> String {
+    format!("{}/jobs", route(mount, internal))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+    Cancelled
+}
+
+/** **Job Status**
+
+    Returned when a job gate is called and by the status route.
+    Result is the gate response when done, or the error when failed
+ */
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobStatus {
+    pub id: String,
+    pub gate: String,
+    pub state: JobState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>
+}
+
+impl JobStatus {
+    /** Response of a finished job */
+    pub fn response<R: serde::de::DeserializeOwned>(self) -> Result<Option<R>, Error> {
+        let parse_error = |err: serde_json::Error| Error::Service(ServiceError::JSONParseError(err.to_string()));
+
+        match self.state {
+            JobState::Running => Ok(None),
+            JobState::Done => serde_json::from_value(self.result.unwrap_or_default())
+                .map(Some)
+                .map_err(parse_error),
+            JobState::Failed => {
+                let response: GateErrorResponse = serde_json::from_value(self.result.unwrap_or_default())
+                    .map_err(parse_error)?;
+                Err(Error::Service(ServiceError::JobFailed(response.error)))
+            },
+            JobState::Cancelled => Err(Error::Service(ServiceError::JobCancelled))
+        }
+    }
+}
+
+struct Job {
+    gate: &'static str,
+    mount: &'static str,
+    internal: bool,
+    caller: String,
+    state: JobState,
+    result: Option<serde_json::Value>,
+    abort: AbortHandle,
+    finished: Option<Instant>
+}
+
+impl Job {
+    fn status(&self, id: &str) -> JobStatus {
+        JobStatus { id: id.to_string(), gate: self.gate.to_string(), state: self.state, result: self.result.clone() }
+    }
+}
+
+struct StoreState {
+    jobs: HashMap<String, Job>,
+    cleaned: Instant
+}
+
+/** **Job Store**
+
+    Jobs of all mounted services, keyed by the mount, route and caller.
+    Ids are random, jobs of other callers are not found as unknown ones.
+    Jobs run on the worker thread which accepted them, gate futures need not be ```Send```.
+    The store is shared by actix workers, so any worker answers status and cancel calls
+ */
+pub(crate) struct JobStore {
+    retention: Duration,
+    state: Mutex<StoreState>
+}
+
+// Fails the job if its runner stops before recording the result
+struct Runner {
+    store: Arc<JobStore>,
+    id: String
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        let error = GateErrorResponse::from(Error::Custom(Some("Job runner stopped".to_string())));
+        self.store.finish(&self.id, JobState::Failed, serde_json::to_value(error).ok());
+    }
+}
+
+impl JobStore {
+    pub(crate) fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            state: Mutex::new(StoreState { jobs: HashMap::new(), cleaned: Instant::now() })
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StoreState> {
+        let mut state = self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let now = Instant::now();
+        if now.duration_since(state.cleaned) >= CLEANUP_INTERVAL {
+            let retention = self.retention;
+            state.jobs.retain(|_, job| job.finished.is_none_or(|finished| now.duration_since(finished) < retention));
+            state.cleaned = now;
+        }
+
+        state
+    }
+
+    /** Runs the gate in background. Result is the response or the error json */
+    pub(crate) fn start<F>(self: &Arc<Self>, gate: &GateMeta, mount: &'static str, internal: bool, caller: String, process: F)
+        -> Result<JobStatus, Error>
+    where
+        F: Future<Output = Result<serde_json::Value, serde_json::Value>> + 'static
+    {
+        let id = job_id()?;
+        let task = actix_web::rt::spawn(process);
+
+        let job = Job {
+            gate: gate.name,
+            mount,
+            internal,
+            caller,
+            state: JobState::Running,
+            result: None,
+            abort: task.abort_handle(),
+            finished: None
+        };
+        let status = job.status(&id);
+        self.lock().jobs.insert(id.clone(), job);
+
+        info!("Job {} of gate {} started", id, gate.name);
+
+        let runner = Runner { store: self.clone(), id };
+        actix_web::rt::spawn(async move {
+            let (state, result) = match task.await {
+                Ok(Ok(response)) => (JobState::Done, Some(response)),
+                Ok(Err(err)) => (JobState::Failed, Some(err)),
+                Err(err) if err.is_cancelled() => (JobState::Cancelled, None),
+                Err(err) => {
+                    error!("Job {} panicked. {}", runner.id, err);
+                    let error = GateErrorResponse::from(Error::Custom(Some("Job panicked".to_string())));
+                    (JobState::Failed, serde_json::to_value(error).ok())
+                }
+            };
+
+            runner.store.finish(&runner.id, state, result);
+        });
+
+        Ok(status)
+    }
+
+    // Only running jobs are finished, the first result stays
+    fn finish(&self, id: &str, state: JobState, result: Option<serde_json::Value>) {
+        if let Some(job) = self.lock().jobs.get_mut(id)
+            && job.state == JobState::Running
+        {
+            debug!("Job {} of gate {} is {:?}", id, job.gate, state);
+            job.state = state;
+            job.result = result;
+            job.finished = Some(Instant::now());
+        }
+    }
+
+    fn status(&self, id: &str, mount: &str, internal: bool, caller: &str) -> Option<JobStatus> {
+        self.lock().jobs.get(id)
+            .filter(|job| job.mount == mount && job.internal == internal && job.caller == caller)
+            .map(|job| job.status(id))
+    }
+
+    fn cancel(&self, id: &str, mount: &str, internal: bool, caller: &str) -> Option<JobStatus> {
+        let status = self.status(id, mount, internal, caller)?;
+
+        if status.state == JobState::Running {
+            if let Some(job) = self.lock().jobs.get(id) {
+                job.abort.abort();
+            }
+            self.finish(id, JobState::Cancelled, None);
+            info!("Job {} of gate {} cancelled", id, status.gate);
+        }
+
+        self.status(id, mount, internal, caller)
+    }
+}
+
+// Ids are not guessable, status and cancel calls know them from the start
+fn job_id() -> Result<String, Error> {
+    let mut bytes = [0u8; 16];
+    openssl::rand::rand_bytes(&mut bytes)
+        .map_err(|err| Error::Custom(Some(format!("Job id can't be generated. {err}"))))?;
+
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/** Caller of job routes. Jobs are scoped by the peer address, as idempotency keys are */
+pub(crate) fn caller(request: &HttpRequest) -> String {
+    request.connection_info().peer_addr().unwrap_or_default().to_string()
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(GateErrorResponse::from(Error::Service(ServiceError::JobNotFound)))
+}
+
+pub(crate) async fn status(id: web::Path<String>, jobs: web::Data<JobStore>, request: HttpRequest, mount: &'static str, internal: bool)
+    -> HttpResponse
+{
+    match jobs.status(&id, mount, internal, &caller(&request)) {
+        Some(status) => HttpResponse::Ok().json(status),
+        None => not_found()
+    }
+}
+
+pub(crate) async fn cancel(id: web::Path<String>, jobs: web::Data<JobStore>, request: HttpRequest, mount: &'static str, internal: bool)
+    -> HttpResponse
+{
+    match jobs.cancel(&id, mount, internal, &caller(&request)) {
+        Some(status) => HttpResponse::Ok().json(status),
+        None => not_found()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+    use actix_web::{App, test::{self, TestRequest}};
+    use serde_json::json;
+
+    use super::*;
+
+    const GATE: GateMeta = GateMeta::new("Export");
+    const CALLER: &str = "10.0.0.1";
+
+    // Lets the spawned job and its runner record the result
+    async fn settle(store: &JobStore, id: &str) -> JobStatus {
+        for _ in 0..100 {
+            match store.status(id, "", false, CALLER) {
+                Some(status) if status.state != JobState::Running => return status,
+                _ => actix_web::rt::task::yield_now().await
+            }
+        }
+        panic!("job {id} did not finish")
+    }
+
+    #[actix_web::test]
+    async fn finished_job_keeps_result() {
+        let store = Arc::new(JobStore::new(JOB_RETENTION));
+        let started = store.start(&GATE, "", false, CALLER.to_string(), async { Ok(json!({ "url": "done" })) }).unwrap();
+        assert_eq!(started.state, JobState::Running);
+
+        let status = settle(&store, &started.id).await;
+        assert_eq!(status.state, JobState::Done);
+        assert_eq!(status.response::<serde_json::Value>().unwrap(), Some(json!({ "url": "done" })));
+    }
+
+    #[actix_web::test]
+    async fn failed_job_keeps_error() {
+        let store = Arc::new(JobStore::new(JOB_RETENTION));
+        let error = serde_json::to_value(GateErrorResponse::from(Error::Custom(Some("broken".to_string())))).unwrap();
+        let started = store.start(&GATE, "", false, CALLER.to_string(), async move { Err(error) }).unwrap();
+
+        let status = settle(&store, &started.id).await;
+        assert_eq!(status.state, JobState::Failed);
+        assert!(matches!(status.response::<serde_json::Value>(), Err(Error::Service(ServiceError::JobFailed(_)))));
+    }
+
+    #[actix_web::test]
+    async fn cancelled_job_stays_cancelled() {
+        let store = Arc::new(JobStore::new(JOB_RETENTION));
+        let started = store.start(&GATE, "", false, CALLER.to_string(), pending()).unwrap();
+
+        let status = store.cancel(&started.id, "", false, CALLER).unwrap();
+        assert_eq!(status.state, JobState::Cancelled);
+
+        // Aborted runner must not override the first result
+        let status = settle(&store, &started.id).await;
+        assert_eq!(status.state, JobState::Cancelled);
+        assert!(matches!(status.response::<serde_json::Value>(), Err(Error::Service(ServiceError::JobCancelled))));
+    }
+
+    #[actix_web::test]
+    async fn jobs_are_scoped_by_mount_and_route() {
+        let store = Arc::new(JobStore::new(JOB_RETENTION));
+        let started = store.start(&GATE, "/users", false, CALLER.to_string(), pending()).unwrap();
+
+        assert!(store.status(&started.id, "/users", false, CALLER).is_some());
+        assert!(store.status(&started.id, "/users", true, CALLER).is_none());
+        assert!(store.status(&started.id, "/orders", false, CALLER).is_none());
+        assert!(store.cancel(&started.id, "/orders", false, CALLER).is_none());
+        assert_eq!(store.status(&started.id, "/users", false, CALLER).unwrap().state, JobState::Running);
+    }
+
+    #[actix_web::test]
+    async fn finished_jobs_expire_after_retention() {
+        let store = Arc::new(JobStore::new(Duration::ZERO));
+        let running = store.start(&GATE, "", false, CALLER.to_string(), pending()).unwrap();
+        let finished = store.start(&GATE, "", false, CALLER.to_string(), async { Ok(json!(null)) }).unwrap();
+        settle(&store, &finished.id).await;
+
+        let mut state = store.state.lock().unwrap();
+        state.cleaned = Instant::now().checked_sub(CLEANUP_INTERVAL).unwrap();
+        drop(state);
+
+        assert!(store.status(&finished.id, "", false, CALLER).is_none());
+        assert!(store.status(&running.id, "", false, CALLER).is_some());
+    }
+
+    #[test]
+    fn running_job_has_no_response() {
+        let status = JobStatus { id: "1".to_string(), gate: "Export".to_string(), state: JobState::Running, result: None };
+        assert_eq!(status.clone().response::<serde_json::Value>().unwrap(), None);
+
+        let status = JobStatus { state: JobState::Done, result: Some(json!("text")), ..status };
+        assert!(matches!(status.response::<u64>(), Err(Error::Service(ServiceError::JSONParseError(_)))));
+    }
+
+    #[actix_web::test]
+    async fn jobs_of_other_callers_are_not_found() {
+        let store = Arc::new(JobStore::new(JOB_RETENTION));
+        let started = store.start(&GATE, "", false, CALLER.to_string(), pending()).unwrap();
+
+        let app = test::init_service(App::new()
+            .app_data(web::Data::from(store.clone()))
+            .route("/jobs/{id}", web::get().to(|id, jobs, request| status(id, jobs, request, "", false)))
+            .route("/jobs/{id}", web::delete().to(|id, jobs, request| cancel(id, jobs, request, "", false)))
+        ).await;
+
+        let uri = format!("/jobs/{}", started.id);
+        let request = |request: TestRequest, peer: &str| request.uri(&uri).peer_addr(peer.parse().unwrap()).to_request();
+
+        let response = test::call_service(&app, request(TestRequest::get(), "10.0.0.2:4000")).await;
+        assert_eq!(response.status(), 404);
+        let response = test::call_service(&app, request(TestRequest::delete(), "10.0.0.2:4000")).await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(store.status(&started.id, "", false, CALLER).unwrap().state, JobState::Running);
+
+        // New connections of the caller come from other ports
+        let response = test::call_service(&app, request(TestRequest::get(), "10.0.0.1:4001")).await;
+        assert_eq!(response.status(), 200);
+        let response = test::call_service(&app, request(TestRequest::delete(), "10.0.0.1:4002")).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(store.status(&started.id, "", false, CALLER).unwrap().state, JobState::Cancelled);
+    }
+
+    #[test]
+    fn job_ids_are_random() {
+        let (first, second) = (job_id().unwrap(), job_id().unwrap());
+        assert_eq!(first.len(), 32);
+        assert!(first.chars().all(|char| char.is_ascii_hexdigit()));
+        assert_ne!(first, second);
+    }
+}
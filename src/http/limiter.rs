@@ -0,0 +1,304 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use actix_web::{HttpMessage, HttpResponse, body::BoxBody, dev::{self, ServiceResponse}, http::header, middleware, web};
+use log::info;
+
+use crate::{Error, Origin, common_gate::GateErrorResponse, error::ServiceError, http::GateMeta};
+
+// Buckets are cleaned up after the limit
+const MAX_BUCKETS: usize = 10_000;
+
+type Identity = Arc<dyn Fn(&dev::ServiceRequest) -> Option<String> + Send + Sync>;
+
+/** Token bucket limit.
+    Holds up to ```burst``` tokens, refilled at ```per_second``` rate.
+    Each request takes one token
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub burst: u32,
+    pub per_second: f64
+}
+
+impl Limit {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+struct Bucket {
+    limit: Limit,
+    tokens: f64,
+    updated: Instant
+}
+
+impl Bucket {
+    fn new(limit: Limit, now: Instant) -> Self {
+        Self { limit, tokens: limit.burst as f64, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.updated = now;
+    }
+
+    // Time until the next token
+    fn wait(&self) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            None
+        } else if self.limit.per_second > 0.0 {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.limit.per_second))
+        } else {
+            Some(Duration::MAX)
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.burst as f64
+    }
+}
+
+/** **Rate Limiter**
+
+    Token bucket rate limiter of an Instance.
+    Limits are applied per peer origin, per caller identity and per gate name.
+    Rejected requests are answered with ```429``` and ```Retry-After``` header
+    ```ignore
+    RateLimiter::new()
+        .set_origin_limit(Limit::new(20, 10.0))
+        .set_origin_limit_of(Origin::IP { addr: "10.0.0.5", port: 0 }, Limit::new(200, 100.0))
+        .set_caller_limit(Limit::new(5, 1.0), |request|
+            request.headers().get("x-caller").and_then(|v| v.to_str().ok()).map(String::from))
+        .set_gate_limit("Msg", Limit::new(100, 50.0))
+    ```
+ */
+#[derive(Default)]
+pub struct RateLimiter {
+    origin_limit: Option<Limit>,
+    origin_limits: HashMap<String, Limit>,
+    caller_limit: Option<(Limit, Identity)>,
+    gate_limits: HashMap<String, Limit>,
+    buckets: Mutex<HashMap<String, Bucket>>
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Limit for each peer address */
+    pub fn set_origin_limit(mut self, limit: Limit) -> Self {
+        self.origin_limit = Some(limit);
+        self
+    }
+
+    /** Limit of the origin instead of the one for each peer address. Port is ignored */
+    pub fn set_origin_limit_of(mut self, origin: Origin, limit: Limit) -> Self {
+        self.origin_limits.insert(origin.host().to_string(), limit);
+        self
+    }
+
+    /** Limit for each caller. Requests without identity are not limited by caller */
+    pub fn set_caller_limit<F>(mut self, limit: Limit, identity: F) -> Self
+        where F: Fn(&dev::ServiceRequest) -> Option<String> + Send + Sync + 'static
+    {
+        self.caller_limit = Some((limit, Arc::new(identity)));
+        self
+    }
+
+    /** Limit for a gate, shared by all callers */
+    pub fn set_gate_limit(mut self, gate: &str, limit: Limit) -> Self {
+        self.gate_limits.insert(gate.to_string(), limit);
+        self
+    }
+
+    fn limits(&self, request: &dev::ServiceRequest) -> Vec<(String, Limit)> {
+        let mut limits = Vec::new();
+
+        if let Some(origin) = request.connection_info().peer_addr()
+            && let Some(limit) = self.origin_limits.get(origin).copied().or(self.origin_limit)
+        {
+            limits.push((format!("origin:{origin}"), limit));
+        }
+
+        if let Some((limit, identity)) = &self.caller_limit
+            && let Some(caller) = identity(request)
+        {
+            limits.push((format!("caller:{caller}"), *limit));
+        }
+
+        if let Some(gate) = request.extensions().get::<GateMeta>()
+            && let Some(limit) = self.gate_limits.get(gate.name)
+        {
+            limits.push((format!("gate:{}:{}", request.path(), gate.name), *limit));
+        }
+
+        limits
+    }
+
+    // Gate limits need the gate name from the request body
+    pub(crate) fn limits_gates(&self) -> bool {
+        !self.gate_limits.is_empty()
+    }
+
+    /** Takes a token from every matching bucket.
+        If any of them is empty, nothing is taken and waiting time is returned
+     */
+    pub(crate) fn check(&self, request: &dev::ServiceRequest) -> Result<(), Duration> {
+        let limits = self.limits(request);
+        if limits.is_empty() {
+            return Ok(())
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Full buckets are equal to new ones
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+        }
+
+        let wait = limits.iter()
+            .filter_map(|(key, limit)| {
+                let bucket = buckets.entry(key.clone())
+                    .or_insert_with(|| Bucket::new(*limit, now));
+
+                bucket.refill(now);
+                bucket.wait()
+            })
+            .max();
+
+        if let Some(wait) = wait {
+            return Err(wait)
+        }
+
+        for (key, _) in &limits {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) async fn limit_middleware(
+    request: dev::ServiceRequest,
+    next: middleware::Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error>
+{
+    let limited = request.app_data::<web::Data<RateLimiter>>()
+        .map(|limiter| limiter.check(&request))
+        .unwrap_or(Ok(()));
+
+    if let Err(retry_after) = limited {
+        info!("Request to {} is rate limited", request.path());
+
+        // Retry-After is in whole seconds
+        let seconds = retry_after.as_secs_f64().ceil().clamp(1.0, u32::MAX as f64) as u64;
+
+        return Ok(request.into_response(
+            HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, seconds))
+                .json(GateErrorResponse::from(Error::Service(ServiceError::RateLimited {
+                    retry_after: Some(Duration::from_secs(seconds))
+                })))
+        ));
+    }
+
+    next.call(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn request(peer: &str) -> dev::ServiceRequest {
+        TestRequest::default()
+            .peer_addr(peer.parse().unwrap())
+            .to_srv_request()
+    }
+
+    #[test]
+    fn bucket_allows_burst() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(Limit::new(3, 1.0), now);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.wait(), None);
+            bucket.tokens -= 1.0;
+        }
+
+        assert_eq!(bucket.wait(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn bucket_refills_up_to_burst() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(Limit::new(2, 4.0), now);
+        bucket.tokens = 0.0;
+
+        bucket.refill(now + Duration::from_millis(250));
+        assert_eq!(bucket.tokens, 1.0);
+        assert!(!bucket.is_full());
+
+        bucket.refill(now + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 2.0);
+        assert!(bucket.is_full());
+    }
+
+    #[test]
+    fn bucket_without_rate_never_refills() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(Limit::new(1, 0.0), now);
+        bucket.tokens = 0.0;
+
+        bucket.refill(now + Duration::from_secs(60));
+        assert_eq!(bucket.wait(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn origins_have_separate_buckets() {
+        let limiter = RateLimiter::new().set_origin_limit(Limit::new(1, 0.0));
+
+        assert!(limiter.check(&request("10.0.0.1:1000")).is_ok());
+        assert!(limiter.check(&request("10.0.0.1:1001")).is_err());
+        assert!(limiter.check(&request("10.0.0.2:1000")).is_ok());
+    }
+
+    #[test]
+    fn origin_limit_overrides_default() {
+        let limiter = RateLimiter::new()
+            .set_origin_limit(Limit::new(1, 0.0))
+            .set_origin_limit_of(Origin::IP { addr: "10.0.0.5", port: 0 }, Limit::new(3, 0.0));
+
+        for _ in 0..3 {
+            assert!(limiter.check(&request("10.0.0.5:1000")).is_ok());
+        }
+        assert!(limiter.check(&request("10.0.0.5:1000")).is_err());
+
+        assert!(limiter.check(&request("10.0.0.6:1000")).is_ok());
+        assert!(limiter.check(&request("10.0.0.6:1000")).is_err());
+    }
+
+    #[test]
+    fn rejected_request_takes_no_tokens() {
+        let limiter = RateLimiter::new()
+            .set_origin_limit(Limit::new(2, 0.0))
+            .set_caller_limit(Limit::new(1, 0.0), |_| Some("caller".to_string()));
+
+        assert!(limiter.check(&request("10.0.0.1:1000")).is_ok());
+        assert!(limiter.check(&request("10.0.0.1:1000")).is_err());
+
+        // Origin bucket kept its second token
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets["origin:10.0.0.1"].tokens, 1.0);
+    }
+}
@@ -4,9 +4,33 @@ pub mod instance;
 pub mod error;
 pub mod gate;
 pub mod gate_macro;
+pub mod policy;
+pub mod limiter;
+pub mod concurrency;
+pub mod breaker;
+pub mod balancer;
+pub(crate) mod cache;
+pub mod idempotency;
+pub mod version;
+pub mod schema;
+pub mod validation;
+pub mod describe;
+pub mod tls;
+pub mod schedule;
+pub mod events;
+pub mod events_macro;
+pub mod jobs;
+pub mod request;
 
 pub use context::Context;
-pub use worker::Worker;
+pub use worker::{Reloadable, Worker};
 pub use instance::Instance;
 pub use error::Error;
+pub use policy::RoutePolicy;
+pub use limiter::{Limit, RateLimiter};
+pub use concurrency::{Concurrency, Priority};
+pub use breaker::{Circuit, CircuitBreaker};
+pub use balancer::{Balancer, Strategy};
+pub use schedule::{Schedule, Task};
+pub use request::RequestContext;
 pub use gate::*;
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use actix_web::dev;
+
+use crate::{Origin, origin::AllowedOrigins};
+
+type Auth = Arc<dyn Fn(&dev::ServiceRequest) -> bool + Send + Sync>;
+
+/** **Route Policy**
+
+    Origin and auth policy of public or internal gates route.
+    Is checked before the worker middleware.
+ */
+#[derive(Clone, Default)]
+pub struct RoutePolicy {
+    pub(crate) allowed_origins: Option<Arc<AllowedOrigins>>,
+    pub(crate) auth: Option<Auth>
+}
+
+impl RoutePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Only requests from allowed origins are accepted */
+    pub fn set_allowed_origins(mut self, allowed_origins: Vec<Origin>) -> Self {
+        self.allowed_origins = Some(Arc::new(AllowedOrigins::from(&allowed_origins)));
+        self
+    }
+
+    /** Requests rejected by auth function are answered with ```401``` */
+    pub fn set_auth<F>(mut self, auth: F) -> Self
+        where F: Fn(&dev::ServiceRequest) -> bool + Send + Sync + 'static
+    {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct RoutePolicies {
+    pub(crate) public: RoutePolicy,
+    pub(crate) internal: RoutePolicy
+}
+
+impl RoutePolicies {
+    pub(crate) fn get(&self, internal: bool) -> &RoutePolicy {
+        if internal { &self.internal } else { &self.public }
+    }
+}
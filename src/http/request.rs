@@ -0,0 +1,192 @@
+use std::{future::Future, net::SocketAddr, time::{Duration, Instant}};
+use actix_web::{HttpMessage, HttpRequest, dev::{Extensions, ServiceRequest}, http::header::HeaderMap};
+use log::warn;
+
+use crate::http::idempotency::idempotency_key;
+
+/** Header with the trace id, generated when missing */
+pub const TRACE_ID_HEADER: &str = "rmt-trace-id";
+/** Header with milliseconds the caller waits for the response */
+pub const DEADLINE_HEADER: &str = "rmt-deadline-ms";
+
+// Trace of the gate request being processed, forwarded by context calls
+#[derive(Clone, Debug)]
+pub(crate) struct Trace {
+    pub(crate) trace_id: String,
+    pub(crate) deadline: Option<Instant>
+}
+
+tokio::task_local! {
+    static TRACE: Trace;
+}
+
+impl Trace {
+    /** Trace of the current gate request, if called while a gate is processed */
+    pub(crate) fn current() -> Option<Self> {
+        TRACE.try_with(Clone::clone).ok()
+    }
+
+    /** Runs the gate processing with the trace */
+    pub(crate) fn scope<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        TRACE.scope(self, future)
+    }
+}
+
+/** **Request Context**
+
+    Per-request data passed to ```Gate::process```, populated by the instance.
+    Middleware adds typed values, e.g. the authenticated caller, with ```RequestContext::extend```
+    ```ignore
+    async fn middleware_pre(&self, request: ServiceRequest) -> Result<ServiceRequest, Error> {
+        let caller = Caller(token_subject(request.headers())?);
+        RequestContext::extend(&request, caller);
+        Ok(request)
+    }
+
+    #[http_gate(MyService::Msg | MyWorker)]
+    async fn process(self, worker: &Self::W, context: &RequestContext) -> Result<Self::Response, Error> {
+        let caller = context.get::<Caller>().ok_or(Error::Service(ServiceError::Unauthorized))?;
+        // implementation
+    }
+    ```
+ */
+#[derive(Debug)]
+pub struct RequestContext {
+    pub peer_addr: Option<SocketAddr>,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub internal: bool,
+    pub trace_id: String,
+    pub deadline: Option<Instant>,
+    extensions: Extensions
+}
+
+impl RequestContext {
+    pub(crate) fn new(request: &HttpRequest, internal: bool) -> Self {
+        let header = |name| request.headers().get(name).and_then(|value| value.to_str().ok());
+
+        Self {
+            peer_addr: request.peer_addr(),
+            path: request.path().to_string(),
+            headers: request.headers().clone(),
+            internal,
+            trace_id: header(TRACE_ID_HEADER).map_or_else(idempotency_key, str::to_string),
+            deadline: header(DEADLINE_HEADER)
+                .and_then(|millis| millis.parse().ok())
+                // Deadlines too far to represent are no deadline
+                .and_then(|millis| Instant::now().checked_add(Duration::from_millis(millis))),
+            extensions: Extensions::new()
+        }
+    }
+
+    /** Trace forwarded by calls of the gate. Jobs are not awaited by the caller, so they keep no deadline */
+    pub(crate) fn trace(&self, job: bool) -> Trace {
+        Trace { trace_id: self.trace_id.clone(), deadline: if job { None } else { self.deadline } }
+    }
+
+    /** Time left until the caller stops waiting */
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /** Replaces the value of the same type */
+    pub fn insert<T: 'static>(&mut self, value: T) {
+        self.extensions.insert(value);
+    }
+
+    /** Adds the value to the context of a gate request, used in middleware */
+    pub fn extend<T: 'static>(request: &ServiceRequest, value: T) {
+        match request.extensions_mut().get_mut::<Self>() {
+            Some(context) => context.insert(value),
+            None => warn!("No request context to extend on {}", request.path())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Caller(&'static str);
+
+    #[test]
+    fn context_is_read_from_request() {
+        let request = TestRequest::post()
+            .uri("/users/request")
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header((TRACE_ID_HEADER, "trace-1"))
+            .insert_header((DEADLINE_HEADER, "2000"))
+            .to_http_request();
+
+        let context = RequestContext::new(&request, true);
+        assert_eq!(context.peer_addr, Some("10.0.0.1:4000".parse().unwrap()));
+        assert_eq!(context.path, "/users/request");
+        assert_eq!(context.trace_id, "trace-1");
+        assert!(context.internal);
+        assert!(context.remaining().is_some_and(|left| left > Duration::from_millis(1000) && left <= Duration::from_millis(2000)));
+    }
+
+    #[test]
+    fn trace_id_is_generated_and_invalid_deadline_ignored() {
+        let request = TestRequest::post()
+            .insert_header((DEADLINE_HEADER, "soon"))
+            .to_http_request();
+
+        let first = RequestContext::new(&request, false);
+        let second = RequestContext::new(&request, false);
+        assert!(!first.trace_id.is_empty());
+        assert_ne!(first.trace_id, second.trace_id);
+        assert_eq!(first.deadline, None);
+
+        // Overflows Instant on some platforms, without a panic
+        let request = TestRequest::post()
+            .insert_header((DEADLINE_HEADER, u64::MAX.to_string()))
+            .to_http_request();
+        let deadline = RequestContext::new(&request, false).deadline;
+        assert!(deadline.is_none_or(|deadline| deadline > Instant::now() + Duration::from_secs(3600 * 24 * 365)));
+    }
+
+    #[test]
+    fn middleware_extends_context_of_request() {
+        let request = TestRequest::post().to_srv_request();
+
+        // Without a context the value is dropped
+        RequestContext::extend(&request, Caller("nobody"));
+
+        let context = RequestContext::new(request.request(), false);
+        request.extensions_mut().insert(context);
+        RequestContext::extend(&request, Caller("alice"));
+        RequestContext::extend(&request, Caller("bob"));
+
+        let context = request.extensions_mut().remove::<RequestContext>().unwrap();
+        assert_eq!(context.get::<Caller>(), Some(&Caller("bob")));
+        assert_eq!(context.get::<u32>(), None);
+    }
+
+    #[tokio::test]
+    async fn trace_is_current_inside_of_scope() {
+        let request = TestRequest::post()
+            .insert_header((TRACE_ID_HEADER, "trace-2"))
+            .insert_header((DEADLINE_HEADER, "1000"))
+            .to_http_request();
+        let context = RequestContext::new(&request, false);
+
+        assert!(Trace::current().is_none());
+
+        let trace = context.trace(false).scope(async { Trace::current() }).await.unwrap();
+        assert_eq!(trace.trace_id, "trace-2");
+        assert_eq!(trace.deadline, context.deadline);
+
+        // Jobs outlive the caller, only the trace id is kept
+        let trace = context.trace(true).scope(async { Trace::current() }).await.unwrap();
+        assert_eq!(trace.trace_id, "trace-2");
+        assert_eq!(trace.deadline, None);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{fmt::Display, future::Future, pin::Pin, str::FromStr, sync::Arc, time::{Duration, Instant}};
+use chrono::{Datelike, Local, NaiveDateTime, TimeZone, Timelike};
+use log::{debug, error, info, warn};
+
+use crate::{Error, metrics};
+
+// Cron fields: minute, hour, day of month, month, day of week
+const FIELDS: [(&str, u32, u32); 5] = [("minute", 0, 59), ("hour", 0, 23), ("day", 1, 31), ("month", 1, 12), ("weekday", 0, 7)];
+// Expressions without a matching minute in this period, e.g. February 31
+const SEARCH_DAYS: i64 = 366 * 5;
+
+/** **Cron Expression**
+
+    Minute, hour, day of month, month and day of week in local time.
+    Fields are ```*```, values, ranges and steps, e.g. ```0-59/15 9-17 * * 1-5```.
+    Sunday is 0 or 7. When both days are set, either of them matches
+ */
+#[derive(Clone, Debug)]
+pub struct Cron {
+    expr: String,
+    fields: [u64; 5],
+    any_day: bool,
+    any_weekday: bool
+}
+
+fn parse_field(expr: &str, name: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("invalid {name} {expr:?}");
+    let mut mask = 0u64;
+
+    for item in expr.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (item, 1)
+        };
+
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (from.parse().map_err(|_| invalid())?, to.parse().map_err(|_| invalid())?),
+            // Single value with step runs to the end, as `5/15`
+            None => {
+                let from = range.parse().map_err(|_| invalid())?;
+                (from, if item.contains('/') { max } else { from })
+            }
+        };
+
+        if from < min || to > max || from > to {
+            return Err(invalid())
+        }
+
+        mask |= (from..=to).step_by(step as usize).fold(0, |mask, value| mask | 1 << value);
+    }
+
+    Ok(mask)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        if parts.len() != FIELDS.len() {
+            return Err(format!("cron {expr:?} should have 5 fields"))
+        }
+
+        let mut fields = [0; 5];
+        for (i, (part, (name, min, max))) in parts.iter().zip(FIELDS).enumerate() {
+            fields[i] = parse_field(part, name, min, max)?;
+        }
+
+        // Sunday is both 0 and 7
+        if fields[4] & 1 << 7 != 0 {
+            fields[4] |= 1;
+        }
+
+        Ok(Self { expr: expr.to_string(), fields, any_day: parts[2] == "*", any_weekday: parts[4] == "*" })
+    }
+}
+
+impl Cron {
+    fn matches(&self, field: usize, value: u32) -> bool {
+        self.fields[field] & 1 << value != 0
+    }
+
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let day = self.matches(2, time.day());
+        let weekday = self.matches(4, time.weekday().num_days_from_sunday());
+
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday
+        }
+    }
+
+    /** First matching minute after the given time */
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = time.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let end = start + chrono::Duration::days(SEARCH_DAYS);
+        let mut time = start;
+
+        while time < end {
+            let day = time.date().and_hms_opt(0, 0, 0)?;
+
+            time = if !self.matches(3, time.month()) || !self.matches_day(&time) {
+                day + chrono::Duration::days(1)
+            } else if !self.matches(1, time.hour()) {
+                day + chrono::Duration::hours(time.hour() as i64 + 1)
+            } else if !self.matches(0, time.minute()) {
+                time + chrono::Duration::minutes(1)
+            } else {
+                return Some(time)
+            };
+        }
+
+        None
+    }
+
+    // Time to wait from now. Minutes skipped by a DST change are passed
+    fn until_next(&self) -> Option<Duration> {
+        let now = Local::now();
+        let mut time = now.naive_local();
+
+        loop {
+            time = self.next_after(time)?;
+            if let Some(next) = Local.from_local_datetime(&time).earliest() {
+                return (next - now).to_std().ok()
+            }
+        }
+    }
+}
+
+/** When a task runs */
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron)
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Every(period) => write!(f, "every {:?}", period),
+            Self::Cron(cron) => write!(f, "at {:?}", cron.expr)
+        }
+    }
+}
+
+type Run = Pin<Box<dyn Future<Output = Result<(), Error>>>>;
+type Job<W> = Arc<dyn Fn(W) -> Run + Send + Sync>;
+
+/** **Scheduled Task**
+
+    Background job of a worker, returned by ```Worker::tasks```.
+    Runs of a task never overlap, fire times passed during a run are skipped.
+    Runs are reported as ```rmt.task.<name>.runs```, ```.failures```, ```.skipped``` and ```.duration_ms``` metrics
+    ```ignore
+    fn tasks(&self) -> Vec<Task<Self>> {
+        vec![
+            Task::every("refresh", Duration::from_secs(60), |worker: Self| async move { worker.refresh().await }),
+            Task::cron("cleanup", "0 3 * * *", |worker: Self| async move { worker.cleanup().await })
+        ]
+    }
+    ```
+ */
+pub struct Task<W> {
+    pub name: String,
+    // Invalid cron fails the instance start
+    schedule: Result<Schedule, String>,
+    job: Job<W>
+}
+
+impl<W> Task<W> {
+    fn with_schedule<F, R>(name: impl Into<String>, schedule: Result<Schedule, String>, job: F) -> Self
+    where
+        F: Fn(W) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), Error>> + 'static
+    {
+        Self { name: name.into(), schedule, job: Arc::new(move |worker| Box::pin(job(worker))) }
+    }
+
+    pub fn new<F, R>(name: impl Into<String>, schedule: Schedule, job: F) -> Self
+    where
+        F: Fn(W) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), Error>> + 'static
+    {
+        Self::with_schedule(name, Ok(schedule), job)
+    }
+
+    /** Runs every period, first time after one period. Zero period fails the instance start */
+    pub fn every<F, R>(name: impl Into<String>, period: Duration, job: F) -> Self
+    where
+        F: Fn(W) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), Error>> + 'static
+    {
+        Self::new(name, Schedule::Every(period), job)
+    }
+
+    /** Runs on minutes matching the cron expression */
+    pub fn cron<F, R>(name: impl Into<String>, expr: &str, job: F) -> Self
+    where
+        F: Fn(W) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<(), Error>> + 'static
+    {
+        Self::with_schedule(name, expr.parse().map(Schedule::Cron), job)
+    }
+
+    pub fn schedule(&self) -> Result<&Schedule, &str> {
+        self.schedule.as_ref().map_err(String::as_str)
+    }
+}
+
+// Task with its worker, as registered in the instance
+pub(crate) struct ScheduledTask {
+    name: String,
+    schedule: Result<Schedule, String>,
+    run: Arc<dyn Fn() -> Run + Send + Sync>
+}
+
+impl ScheduledTask {
+    pub(crate) fn new<W: Clone + Send + Sync + 'static>(task: Task<W>, worker: W) -> Self {
+        let job = task.job;
+
+        Self {
+            name: task.name,
+            schedule: task.schedule,
+            run: Arc::new(move || job(worker.clone()))
+        }
+    }
+
+    pub(crate) fn schedule(&self) -> std::io::Result<Schedule> {
+        let schedule = match &self.schedule {
+            Ok(Schedule::Every(period)) if period.is_zero() => Err("period must not be zero".to_string()),
+            schedule => schedule.clone()
+        };
+
+        schedule.map_err(|err| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Task {} has invalid schedule. {}", self.name, err)
+        ))
+    }
+
+    fn report(&self, result: &Result<(), Error>, elapsed: Duration) {
+        let name = &self.name;
+        metrics::increment(&format!("rmt.task.{name}.runs"), 1);
+        metrics::set(&format!("rmt.task.{name}.duration_ms"), elapsed.as_millis() as i64);
+
+        match result {
+            Ok(()) => debug!("Task {} finished in {:?}", name, elapsed),
+            Err(err) => {
+                metrics::increment(&format!("rmt.task.{name}.failures"), 1);
+                error!("Task {} failed in {:?}. {}", name, elapsed, err)
+            }
+        }
+    }
+
+    fn skip(&self, skipped: u32) {
+        if skipped > 0 {
+            metrics::increment(&format!("rmt.task.{}.skipped", self.name), skipped as i64);
+            warn!("Task {} overran, {} runs skipped", self.name, skipped);
+        }
+    }
+
+    /** Runs the task until it is cancelled */
+    pub(crate) async fn run(self, schedule: Schedule) {
+        info!("Task {} scheduled {}", self.name, schedule);
+
+        match schedule {
+            Schedule::Every(period) => {
+                let mut interval = actix_web::rt::time::interval_at(
+                    actix_web::rt::time::Instant::now() + period,
+                    period
+                );
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+                loop {
+                    interval.tick().await;
+
+                    let started = Instant::now();
+                    let result = (self.run)().await;
+                    let elapsed = started.elapsed();
+
+                    self.report(&result, elapsed);
+                    self.skip((elapsed.as_nanos() / period.as_nanos().max(1)) as u32);
+                }
+            },
+            Schedule::Cron(cron) => loop {
+                let Some(wait) = cron.until_next() else {
+                    warn!("Task {} has no next run", self.name);
+                    return
+                };
+                actix_web::rt::time::sleep(wait).await;
+
+                let started = Local::now().naive_local();
+                let result = (self.run)().await;
+                let finished = Local::now().naive_local();
+
+                self.report(&result, (finished - started).to_std().unwrap_or_default());
+
+                // Fire times passed during the run
+                let mut skipped = 0;
+                let mut time = started;
+                while let Some(next) = cron.next_after(time).filter(|next| *next <= finished) {
+                    skipped += 1;
+                    time = next;
+                }
+                self.skip(skipped);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+            .and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn next(expr: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        expr.parse::<Cron>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expr in [
+            "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8",
+            "5-1 * * * *", "*/0 * * * *", "a * * * *", "1,,2 * * * *", "* * * *", "* * * * * *"
+        ] {
+            assert!(expr.parse::<Cron>().is_err(), "{expr} is accepted");
+        }
+    }
+
+    #[test]
+    fn next_minute_is_after_the_time() {
+        let after = time(2024, 1, 1, 10, 7) + chrono::Duration::seconds(30);
+
+        assert_eq!(next("* * * * *", after), Some(time(2024, 1, 1, 10, 8)));
+        assert_eq!(next("7 10 * * *", time(2024, 1, 1, 10, 7)), Some(time(2024, 1, 2, 10, 7)));
+    }
+
+    #[test]
+    fn steps_start_from_range_or_value() {
+        assert_eq!(next("*/15 * * * *", time(2024, 1, 1, 10, 7)), Some(time(2024, 1, 1, 10, 15)));
+        assert_eq!(next("*/15 * * * *", time(2024, 1, 1, 10, 45)), Some(time(2024, 1, 1, 11, 0)));
+        assert_eq!(next("5/20 * * * *", time(2024, 1, 1, 10, 6)), Some(time(2024, 1, 1, 10, 25)));
+        assert_eq!(next("5/20 * * * *", time(2024, 1, 1, 10, 45)), Some(time(2024, 1, 1, 11, 5)));
+    }
+
+    #[test]
+    fn lists_and_ranges() {
+        let expr = "0 9-17/4,20 * * *";
+
+        assert_eq!(next(expr, time(2024, 1, 1, 9, 0)), Some(time(2024, 1, 1, 13, 0)));
+        assert_eq!(next(expr, time(2024, 1, 1, 17, 0)), Some(time(2024, 1, 1, 20, 0)));
+        assert_eq!(next(expr, time(2024, 1, 1, 20, 0)), Some(time(2024, 1, 2, 9, 0)));
+    }
+
+    #[test]
+    fn either_day_or_weekday_matches() {
+        // 2024-01-01 is Monday, 2024-01-12 is Friday
+        assert_eq!(next("0 0 13 * 5", time(2024, 1, 10, 0, 0)), Some(time(2024, 1, 12, 0, 0)));
+        assert_eq!(next("0 0 13 * 5", time(2024, 1, 12, 0, 0)), Some(time(2024, 1, 13, 0, 0)));
+        assert_eq!(next("0 0 13 * *", time(2024, 1, 1, 0, 0)), Some(time(2024, 1, 13, 0, 0)));
+        assert_eq!(next("0 0 * * 1-5", time(2024, 1, 5, 0, 0)), Some(time(2024, 1, 8, 0, 0)));
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        assert_eq!(next("0 0 * * 0", time(2024, 1, 1, 0, 0)), Some(time(2024, 1, 7, 0, 0)));
+        assert_eq!(next("0 0 * * 7", time(2024, 1, 1, 0, 0)), Some(time(2024, 1, 7, 0, 0)));
+    }
+
+    #[test]
+    fn months_and_years_roll_over() {
+        assert_eq!(next("0 0 1 * *", time(2024, 1, 31, 23, 59)), Some(time(2024, 2, 1, 0, 0)));
+        assert_eq!(next("0 0 31 * *", time(2024, 4, 1, 0, 0)), Some(time(2024, 5, 31, 0, 0)));
+        assert_eq!(next("30 23 31 12 *", time(2024, 12, 31, 23, 30)), Some(time(2025, 12, 31, 23, 30)));
+        assert_eq!(next("0 0 29 2 *", time(2024, 3, 1, 0, 0)), Some(time(2028, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 31 2 *", time(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn zero_period_is_rejected() {
+        let task = Task::every("zero", Duration::ZERO, |_: ()| async { Ok(()) });
+        assert!(ScheduledTask::new(task, ()).schedule().is_err());
+
+        let task = Task::every("second", Duration::from_secs(1), |_: ()| async { Ok(()) });
+        assert!(ScheduledTask::new(task, ()).schedule().is_ok());
+    }
+}
@@ -0,0 +1,366 @@
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+use serde::{Deserialize, Serialize};
+
+use crate::http::{Access, FieldMeta, GateMeta, Service};
+
+/** Field of a gate schema, named as serialized.
+    Optional fields and fields with ```#[serde(default)]``` may be missing in payloads 
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GateSchema {
+    pub name: String,
+    pub access: Access,
+    // Runs in background, see ```GateMeta::job```
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub job: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    pub request: Vec<FieldSchema>,
+    pub response: Vec<FieldSchema>
+}
+
+// Attributes are stringified by http_gates!, e.g. ```doc = " Text"``` or ```serde(default)```
+fn normalized(attribute: &str) -> String {
+    attribute.split_whitespace().collect()
+}
+
+fn has_default(attributes: &[&str]) -> bool {
+    attributes.iter()
+        .map(|attribute| normalized(attribute))
+        .any(|attribute| attribute.starts_with("serde(") 
+            && attribute.split(['(', ',', ')']).any(|item| item == "default" || item.starts_with("default=")))
+}
+
+// Value of a serde attribute item, e.g. ```rename``` of ```serde(rename = "id")```
+fn serde_value(attributes: &[&str], key: &str) -> Option<String> {
+    let prefix = format!("{key}=");
+
+    attributes.iter()
+        .map(|attribute| normalized(attribute))
+        .filter_map(|attribute| Some(attribute.strip_prefix("serde(")?.strip_suffix(')')?.to_string()))
+        .find_map(|items| items.split(',')
+            .find_map(|item| item.strip_prefix(&prefix))
+            .map(|value| value.trim_matches('"').to_string()))
+}
+
+// Field name after serde ```rename_all``` rule, field names are snake case
+fn rename_field(name: &str, rule: &str) -> String {
+    match rule {
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_ascii_uppercase(),
+        "PascalCase" | "camelCase" => {
+            let mut renamed = String::new();
+            let mut capitalize = rule == "PascalCase";
+
+            for c in name.chars() {
+                match c {
+                    '_' => capitalize = true,
+                    c if capitalize => { renamed.push(c.to_ascii_uppercase()); capitalize = false; },
+                    c => renamed.push(c)
+                }
+            }
+            renamed
+        },
+        _ => name.to_string()
+    }
+}
+
+/** Serialized name of a gate field, with serde ```rename``` and ```rename_all``` of the gate or service */
+pub fn wire_name(field: &FieldMeta, gate: &GateMeta) -> String {
+    let name = field.name.trim_start_matches("r#");
+
+    serde_value(field.attributes, "rename")
+        .or_else(|| serde_value(gate.attributes, "rename_all")
+            .or_else(|| serde_value(gate.container, "rename_all"))
+            .map(|rule| rename_field(name, &rule)))
+        .unwrap_or_else(|| name.to_string())
+}
+
+// Doc comment lines joined by new lines
+fn doc(attributes: &[&str]) -> Option<String> {
+    let lines: Vec<&str> = attributes.iter()
+        .filter_map(|attribute| attribute.trim().strip_prefix("doc"))
+        .filter_map(|doc| doc.trim_start().strip_prefix('='))
+        .map(|doc| doc.trim())
+        // Doc comments are raw strings
+        .map(|doc| doc.strip_prefix('r').unwrap_or(doc).trim_matches('#').trim_matches('"').trim())
+        .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/** **Service Schema**
+
+    Gates of a service as generated by ```http_gates!```.
+    Gates are sorted by name, so snapshots are stable
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServiceSchema {
+    pub name: String,
+    pub version: String,
+    pub gates: Vec<GateSchema>
+}
+
+impl FieldSchema {
+    /** Schema of a request or response field of the gate.
+        Gate and service attributes are container attributes of the field
+     */
+    pub fn of(field: &FieldMeta, gate: &GateMeta) -> Self {
+        let ty: String = field.ty.split_whitespace().collect();
+        let default = [field.attributes, gate.attributes, gate.container].into_iter().any(has_default);
+
+        Self {
+            name: wire_name(field, gate),
+            required: !ty.starts_with("Option<") && !default,
+            ty,
+            doc: doc(field.attributes)
+        }
+    }
+}
+
+impl From<&GateMeta> for GateSchema {
+    fn from(gate: &GateMeta) -> Self {
+        Self {
+            name: gate.name.to_string(),
+            access: gate.access,
+            job: gate.job,
+            doc: doc(gate.attributes),
+            request: gate.request.iter().map(|field| FieldSchema::of(field, gate)).collect(),
+            response: gate.response.iter().map(|field| FieldSchema::of(field, gate)).collect()
+        }
+    }
+}
+
+impl ServiceSchema {
+    pub fn of<S: Service>() -> Self {
+        let mut gates: Vec<GateSchema> = S::GATES.iter()
+            .map(GateSchema::from)
+            .collect();
+        gates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            name: S::NAME.to_string(),
+            version: S::VERSION.to_string(),
+            gates
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self)
+            .unwrap_or_default()
+    }
+
+    /** Writes schema snapshot to the file */
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json() + "\n")
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(std::io::Error::other)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    AddedGate,
+    RemovedGate,
+    AccessChange,
+    JobChange,
+    AddedField,
+    RemovedField,
+    TypeChange,
+    NewRequiredField,
+    RequiredChange
+}
+
+/** Difference between two schema snapshots */
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: String,
+    pub breaking: bool
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = if self.breaking { "BREAKING" } else { "ok" };
+        write!(f, "{:<9}{:?} {}", severity, self.kind, self.path)
+    }
+}
+
+// Unknown request fields are ignored by the service and unknown response fields by callers,
+// so only missing or changed data breaks the contract
+fn diff_fields(changes: &mut Vec<Change>, path: &str, old: &[FieldSchema], new: &[FieldSchema], request: bool) {
+    let old: BTreeMap<&str, &FieldSchema> = old.iter().map(|field| (field.name.as_str(), field)).collect();
+    let new: BTreeMap<&str, &FieldSchema> = new.iter().map(|field| (field.name.as_str(), field)).collect();
+
+    let mut change = |kind, name: &str, breaking| changes.push(Change {
+        kind,
+        path: format!("{path}.{name}"),
+        breaking
+    });
+
+    for (name, field) in &new {
+        match old.get(name) {
+            None if request && field.required => change(ChangeKind::NewRequiredField, name, true),
+            None => change(ChangeKind::AddedField, name, false),
+            Some(old) if old.ty != field.ty => change(ChangeKind::TypeChange, name, true),
+            Some(old) if old.required != field.required =>
+                change(ChangeKind::RequiredChange, name, request == field.required),
+            Some(_) => ()
+        }
+    }
+
+    for name in old.keys().filter(|name| !new.contains_key(*name)) {
+        change(ChangeKind::RemovedField, name, !request);
+    }
+}
+
+/** Classifies changes from the old schema to the new one */
+pub fn diff(old: &ServiceSchema, new: &ServiceSchema) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let old_gates: BTreeMap<&str, &GateSchema> = old.gates.iter().map(|gate| (gate.name.as_str(), gate)).collect();
+    let new_gates: BTreeMap<&str, &GateSchema> = new.gates.iter().map(|gate| (gate.name.as_str(), gate)).collect();
+
+    for (name, gate) in &new_gates {
+        let Some(old) = old_gates.get(name) else {
+            changes.push(Change { kind: ChangeKind::AddedGate, path: name.to_string(), breaking: false });
+            continue
+        };
+
+        if old.access != gate.access {
+            changes.push(Change { kind: ChangeKind::AccessChange, path: name.to_string(), breaking: true });
+        }
+
+        if old.job != gate.job {
+            changes.push(Change { kind: ChangeKind::JobChange, path: name.to_string(), breaking: true });
+        }
+
+        diff_fields(&mut changes, &format!("{name}.request"), &old.request, &gate.request, true);
+        diff_fields(&mut changes, &format!("{name}.response"), &old.response, &gate.response, false);
+    }
+
+    for name in old_gates.keys().filter(|name| !new_gates.contains_key(*name)) {
+        changes.push(Change { kind: ChangeKind::RemovedGate, path: name.to_string(), breaking: true });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn field(name: &'static str, ty: &'static str, attributes: &'static [&'static str]) -> FieldMeta {
+        FieldMeta { name, ty, attributes }
+    }
+
+    fn schema_field(name: &str, ty: &str, required: bool) -> FieldSchema {
+        FieldSchema { name: name.to_string(), ty: ty.to_string(), required, doc: None }
+    }
+
+    fn service(gates: Vec<GateSchema>) -> ServiceSchema {
+        ServiceSchema { name: "SchemaTest".to_string(), version: "1.0.0".to_string(), gates }
+    }
+
+    fn gate(name: &str, request: Vec<FieldSchema>, response: Vec<FieldSchema>) -> GateSchema {
+        GateSchema { name: name.to_string(), access: Access::Inherit, job: false, doc: None, request, response }
+    }
+
+    fn kinds(changes: &[Change]) -> Vec<(ChangeKind, &str, bool)> {
+        changes.iter().map(|change| (change.kind, change.path.as_str(), change.breaking)).collect()
+    }
+
+    #[test]
+    fn fields_are_named_as_serialized() {
+        const REQUEST: &[FieldMeta] = &[
+            field("user_id", "u64", &[]),
+            field("reply_to", "String", &["serde(rename = \"to\")"]),
+            field("r#type", "String", &[])
+        ];
+        let gate = GateMeta { request: REQUEST, container: &["serde(rename_all = \"camelCase\")"], ..GateMeta::new("Msg") };
+
+        let names: Vec<String> = gate.request.iter().map(|field| wire_name(field, &gate)).collect();
+        assert_eq!(names, vec!["userId", "to", "type"]);
+
+        let gate = GateMeta { attributes: &["serde(rename_all = \"SCREAMING-KEBAB-CASE\")"], ..gate };
+        assert_eq!(wire_name(&REQUEST[0], &gate), "USER-ID");
+    }
+
+    #[test]
+    fn container_default_makes_fields_optional() {
+        const REQUEST: &[FieldMeta] = &[field("msg", "String", &[]), field("count", "u32", &["serde(default)"])];
+        let gate = GateMeta { request: REQUEST, ..GateMeta::new("Msg") };
+
+        assert!(FieldSchema::of(&REQUEST[0], &gate).required);
+        assert!(!FieldSchema::of(&REQUEST[1], &gate).required);
+
+        let gate = GateMeta { attributes: &["serde(default)"], ..gate };
+        assert!(!FieldSchema::of(&REQUEST[0], &gate).required);
+    }
+
+    #[test]
+    fn request_changes_are_classified() {
+        let old = service(vec![gate("Msg", vec![schema_field("msg", "String", true), schema_field("tag", "String", false)], vec![])]);
+        let new = service(vec![gate("Msg", vec![
+            schema_field("msg", "u64", true),
+            schema_field("tag", "String", true),
+            schema_field("reply_to", "String", true),
+            schema_field("limit", "Option<u32>", false)
+        ], vec![])]);
+
+        assert_eq!(kinds(&diff(&old, &new)), vec![
+            (ChangeKind::AddedField, "Msg.request.limit", false),
+            (ChangeKind::TypeChange, "Msg.request.msg", true),
+            (ChangeKind::NewRequiredField, "Msg.request.reply_to", true),
+            (ChangeKind::RequiredChange, "Msg.request.tag", true)
+        ]);
+        assert_eq!(kinds(&diff(&new, &old)), vec![
+            (ChangeKind::TypeChange, "Msg.request.msg", true),
+            (ChangeKind::RequiredChange, "Msg.request.tag", false),
+            (ChangeKind::RemovedField, "Msg.request.limit", false),
+            (ChangeKind::RemovedField, "Msg.request.reply_to", false)
+        ]);
+    }
+
+    #[test]
+    fn response_changes_are_classified() {
+        let old = service(vec![gate("Msg", vec![], vec![schema_field("msg", "String", true)])]);
+        let new = service(vec![gate("Msg", vec![], vec![schema_field("text", "String", true)])]);
+
+        assert_eq!(kinds(&diff(&old, &new)), vec![
+            (ChangeKind::AddedField, "Msg.response.text", false),
+            (ChangeKind::RemovedField, "Msg.response.msg", true)
+        ]);
+    }
+
+    #[test]
+    fn gate_changes_are_classified() {
+        let old = service(vec![gate("Msg", vec![], vec![]), gate("Ping", vec![], vec![])]);
+        let new = service(vec![
+            GateSchema { access: Access::Internal, job: true, ..gate("Msg", vec![], vec![]) },
+            gate("Time", vec![], vec![])
+        ]);
+
+        assert_eq!(kinds(&diff(&old, &new)), vec![
+            (ChangeKind::AccessChange, "Msg", true),
+            (ChangeKind::JobChange, "Msg", true),
+            (ChangeKind::AddedGate, "Time", false),
+            (ChangeKind::RemovedGate, "Ping", true)
+        ]);
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+use std::{io, path::{Path, PathBuf}, sync::{Arc, Mutex, RwLock}, time::{Duration, SystemTime}};
+use log::{error, info};
+use rustls::{
+    DigitallySignedStruct, DistinguishedName, Error as TlsError, RootCertStore, ServerConfig, SignatureScheme,
+    client::danger::HandshakeSignatureValid,
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, UnixTime, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier, danger::{ClientCertVerified, ClientCertVerifier}},
+    sign::CertifiedKey
+};
+
+/** How often watched PEM files are checked for changes */
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+fn pem_error(path: &Path, err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|err| pem_error(path, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| pem_error(path, err))?;
+
+    match certs.is_empty() {
+        true => Err(pem_error(path, "no certificates")),
+        false => Ok(certs)
+    }
+}
+
+/** Certificate chain and private key from PEM files */
+pub fn load_certified_key(cert: &Path, key: &Path) -> io::Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let chain = load_certs(cert)?;
+    let key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|err| pem_error(key, err))?;
+
+    Ok((chain, key))
+}
+
+/** TLS server config from PEM certificate chain and private key */
+pub fn load_server_config(cert: &Path, key: &Path) -> io::Result<ServerConfig> {
+    let (chain, key) = load_certified_key(cert, key)?;
+
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// Current certificate, swapped on reload. Handshakes in progress keep the old one
+#[derive(Debug)]
+struct CertResolver(RwLock<Arc<CertifiedKey>>);
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone())
+    }
+}
+
+// Current client verifier of the CA, swapped on reload
+#[derive(Debug)]
+struct ClientVerifier(RwLock<Arc<dyn ClientCertVerifier>>);
+
+impl ClientVerifier {
+    fn current(&self) -> Arc<dyn ClientCertVerifier> {
+        self.0.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl ClientCertVerifier for ClientVerifier {
+    // Hints are borrowed from the verifier, which can be swapped. Clients send their certificate anyway
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>], now: UnixTime) 
+        -> Result<ClientCertVerified, TlsError> 
+    {
+        self.current().verify_client_cert(end_entity, intermediates, now)
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) 
+        -> Result<HandshakeSignatureValid, TlsError> 
+    {
+        self.current().verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) 
+        -> Result<HandshakeSignatureValid, TlsError> 
+    {
+        self.current().verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.current().supported_verify_schemes()
+    }
+}
+
+/** **Reloadable TLS**
+
+    TLS from PEM certificate chain, private key and optional CA of client certificates.
+    Files are watched by the instance, changed ones are loaded without dropping connections.
+    Old certificates are kept when new ones are invalid
+ */
+pub struct ReloadableTls {
+    cert: PathBuf,
+    key: PathBuf,
+    ca: Option<PathBuf>,
+    provider: Arc<CryptoProvider>,
+    resolver: Arc<CertResolver>,
+    verifier: Option<Arc<ClientVerifier>>,
+    modified: Mutex<Vec<Option<SystemTime>>>
+}
+
+impl ReloadableTls {
+    pub fn load(cert: impl Into<PathBuf>, key: impl Into<PathBuf>, ca: Option<impl Into<PathBuf>>) -> io::Result<Self> {
+        let (cert, key, ca) = (cert.into(), key.into(), ca.map(Into::into));
+        let provider = ServerConfig::builder().crypto_provider().clone();
+
+        let certified_key = Self::certified_key(&provider, &cert, &key)?;
+        let verifier = match &ca {
+            Some(ca) => Some(Arc::new(ClientVerifier(RwLock::new(Self::client_verifier(&provider, ca)?)))),
+            None => None
+        };
+
+        let tls = Self {
+            resolver: Arc::new(CertResolver(RwLock::new(Arc::new(certified_key)))),
+            verifier,
+            modified: Mutex::new(Vec::new()),
+            provider,
+            cert,
+            key,
+            ca
+        };
+        *tls.modified.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = tls.modified_times();
+
+        Ok(tls)
+    }
+
+    fn certified_key(provider: &CryptoProvider, cert: &Path, key: &Path) -> io::Result<CertifiedKey> {
+        let (chain, der) = load_certified_key(cert, key)?;
+
+        CertifiedKey::from_der(chain, der, provider)
+            .map_err(|err| pem_error(key, err))
+    }
+
+    fn client_verifier(provider: &Arc<CryptoProvider>, ca: &Path) -> io::Result<Arc<dyn ClientCertVerifier>> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(ca)? {
+            roots.add(cert).map_err(|err| pem_error(ca, err))?;
+        }
+
+        WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|err| pem_error(ca, err))
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        [Some(&self.cert), Some(&self.key), self.ca.as_ref()].into_iter()
+            .flatten()
+            .map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .collect()
+    }
+
+    /** Server config resolving the current certificate */
+    pub fn server_config(&self) -> io::Result<ServerConfig> {
+        let builder = ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        Ok(match &self.verifier {
+            Some(verifier) => builder.with_client_cert_verifier(verifier.clone()),
+            None => builder.with_no_client_auth()
+        }.with_cert_resolver(self.resolver.clone()))
+    }
+
+    /** Loads all files again. Nothing is swapped if any of them is invalid */
+    pub fn reload(&self) -> io::Result<()> {
+        let certified_key = Self::certified_key(&self.provider, &self.cert, &self.key)?;
+        let verifier = match (&self.ca, &self.verifier) {
+            (Some(ca), Some(_)) => Some(Self::client_verifier(&self.provider, ca)?),
+            _ => None
+        };
+
+        *self.resolver.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(certified_key);
+        if let (Some(current), Some(verifier)) = (&self.verifier, verifier) {
+            *current.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = verifier;
+        }
+
+        Ok(())
+    }
+
+    /** Reloads files when their modification times change */
+    pub async fn watch(self: Arc<Self>) {
+        self.watch_every(RELOAD_INTERVAL).await
+    }
+
+    async fn watch_every(self: Arc<Self>, period: Duration) {
+        let mut interval = actix_web::rt::time::interval(period);
+
+        loop {
+            interval.tick().await;
+
+            let modified = self.modified_times();
+            let mut last = self.modified.lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            if *last == modified {
+                continue
+            }
+
+            // Times are updated on failure too, a partially written file is retried on its next change
+            *last = modified;
+            drop(last);
+            match self.reload() {
+                Ok(()) => info!("TLS certificates reloaded from {}", self.cert.display()),
+                Err(err) => error!("TLS certificates can't be reloaded, old ones are kept. {}", err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use actix_web::{App, HttpServer};
+    use openssl::{asn1::Asn1Time, bn::BigNum, ec::{EcGroup, EcKey}, hash::MessageDigest, nid::Nid, pkey::PKey, x509::{X509, X509NameBuilder}};
+    use crate::http::instance::serve;
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    fn paths(name: &str) -> (PathBuf, PathBuf) {
+        let path = |kind: &str| std::env::temp_dir().join(format!("rmt-tls-{}-{name}-{kind}.pem", std::process::id()));
+        (path("cert"), path("key"))
+    }
+
+    // Self signed certificate of a new key, returns its DER
+    fn write_pair(cert: &Path, key: &Path, name: &str) -> Vec<u8> {
+        let pkey = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&pkey).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+        let certificate = builder.build();
+
+        std::fs::write(cert, certificate.to_pem().unwrap()).unwrap();
+        std::fs::write(key, pkey.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        certificate.to_der().unwrap()
+    }
+
+    fn current(tls: &ReloadableTls) -> Vec<u8> {
+        tls.resolver.0.read().unwrap().cert[0].to_vec()
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        let timeout = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < timeout, "Condition is not met in time");
+            actix_web::rt::time::sleep(PERIOD).await;
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let (cert, key) = paths("invalid");
+        std::fs::write(&cert, "not a certificate").unwrap();
+        std::fs::write(&key, "not a key").unwrap();
+
+        assert!(ReloadableTls::load(&cert, &key, None::<PathBuf>).is_err());
+        assert!(ReloadableTls::load(paths("missing").0, &key, None::<PathBuf>).is_err());
+    }
+
+    #[actix_web::test]
+    async fn rewritten_pair_is_picked_up() {
+        let (cert, key) = paths("rewritten");
+        let first = write_pair(&cert, &key, "first");
+        let tls = Arc::new(ReloadableTls::load(&cert, &key, None::<PathBuf>).unwrap());
+        assert_eq!(current(&tls), first);
+
+        let watch = actix_web::rt::spawn(tls.clone().watch_every(PERIOD));
+        let second = write_pair(&cert, &key, "second");
+        wait_until(|| current(&tls) == second).await;
+        watch.abort();
+
+        assert!(tls.server_config().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn invalid_replacement_keeps_old_config() {
+        let (cert, key) = paths("replaced");
+        let first = write_pair(&cert, &key, "first");
+        let tls = Arc::new(ReloadableTls::load(&cert, &key, None::<PathBuf>).unwrap());
+        let before = tls.modified_times();
+
+        let watch = actix_web::rt::spawn(tls.clone().watch_every(PERIOD));
+        std::fs::write(&cert, "-----BEGIN CERTIFICATE-----\ntruncated").unwrap();
+        wait_until(|| *tls.modified.lock().unwrap() != before).await;
+        watch.abort();
+
+        assert!(tls.reload().is_err());
+        assert_eq!(current(&tls), first);
+        assert!(tls.server_config().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn watch_stops_with_server() {
+        let (cert, key) = paths("stopped");
+        write_pair(&cert, &key, "stopped");
+        let tls = Arc::new(ReloadableTls::load(&cert, &key, None::<PathBuf>).unwrap());
+
+        let server = HttpServer::new(App::new)
+            .workers(1)
+            .disable_signals()
+            .bind_rustls_0_23(("127.0.0.1", 0), tls.server_config().unwrap())
+            .unwrap()
+            .run();
+        let handle = server.handle();
+        let watch = actix_web::rt::spawn(tls.clone().watch_every(PERIOD));
+        let serving = actix_web::rt::spawn(serve(server, vec![watch]));
+        assert_eq!(Arc::strong_count(&tls), 2);
+
+        handle.stop(true).await;
+        serving.await.unwrap().unwrap();
+
+        // The cancelled watcher drops its reference
+        wait_until(|| Arc::strong_count(&tls) == 1).await;
+    }
+}
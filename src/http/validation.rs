@@ -0,0 +1,224 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Display, sync::Mutex};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::http::{GateMeta, schema::wire_name};
+
+/** Failed validation rule of a request field */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: String) -> Self {
+        Self { field: field.to_string(), message }
+    }
+
+    /** Error of a request field, reported by its serialized name */
+    pub fn of(gate: &GateMeta, field: &str, message: String) -> Self {
+        let name = gate.request.iter()
+            .find(|meta| meta.name == field)
+            .map(|meta| wire_name(meta, gate))
+            .unwrap_or_else(|| field.to_string());
+
+        Self { field: name, message }
+    }
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/** Values with length. ```None``` skips validation */
+pub trait Length {
+    fn length(&self) -> Option<usize>;
+}
+
+/** Values matched by patterns. ```None``` skips validation */
+pub trait Text {
+    fn text(&self) -> Option<&str>;
+}
+
+/** Values compared with bounds. ```None``` skips validation */
+pub trait Bounded<B> {
+    fn bounded(&self) -> Option<&B>;
+}
+
+impl Length for str {
+    fn length(&self) -> Option<usize> { Some(self.chars().count()) }
+}
+
+impl Length for String {
+    fn length(&self) -> Option<usize> { self.as_str().length() }
+}
+
+impl<T> Length for [T] {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<T> Length for Vec<T> {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<T> Length for BTreeSet<T> {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<T, S> Length for HashSet<T, S> {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<K, V> Length for BTreeMap<K, V> {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<K, V, S> Length for HashMap<K, V, S> {
+    fn length(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl<T: Length> Length for Option<T> {
+    fn length(&self) -> Option<usize> { self.as_ref()?.length() }
+}
+
+impl Text for str {
+    fn text(&self) -> Option<&str> { Some(self) }
+}
+
+impl Text for String {
+    fn text(&self) -> Option<&str> { Some(self) }
+}
+
+impl<T: Text> Text for Option<T> {
+    fn text(&self) -> Option<&str> { self.as_ref()?.text() }
+}
+
+macro_rules! bounded {
+    ($($ty:ty),*) => {
+        $(
+            impl Bounded<$ty> for $ty {
+                fn bounded(&self) -> Option<&$ty> { Some(self) }
+            }
+
+            impl Bounded<$ty> for Option<$ty> {
+                fn bounded(&self) -> Option<&$ty> { self.as_ref() }
+            }
+        )*
+    };
+}
+
+bounded!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/** Length in characters or items, both bounds inclusive */
+pub fn len<T: Length + ?Sized>(value: &T, min: usize, max: usize) -> Result<(), String> {
+    match value.length() {
+        Some(length) if length < min || length > max =>
+            Err(format!("length {length} is out of range {min}..={max}")),
+        _ => Ok(())
+    }
+}
+
+/** Both bounds inclusive */
+pub fn range<B, T>(value: &T, min: B, max: B) -> Result<(), String>
+where
+    B: PartialOrd + Display,
+    T: Bounded<B> + ?Sized,
+{
+    match value.bounded() {
+        Some(value) if *value < min || *value > max =>
+            Err(format!("{value} is out of range {min}..={max}")),
+        _ => Ok(())
+    }
+}
+
+pub fn non_empty<T: Length + ?Sized>(value: &T) -> Result<(), String> {
+    match value.length() {
+        Some(0) => Err("must not be empty".to_string()),
+        _ => Ok(())
+    }
+}
+
+/** Whole value must match the pattern. Patterns are compiled once */
+pub fn regex<T: Text + ?Sized>(value: &T, pattern: &'static str) -> Result<(), String> {
+    static PATTERNS: Mutex<BTreeMap<&'static str, Regex>> = Mutex::new(BTreeMap::new());
+
+    let Some(text) = value.text() else {
+        return Ok(())
+    };
+
+    // Cloned regex shares the compiled pattern, matching runs without the lock
+    let regex = {
+        let mut patterns = PATTERNS.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match patterns.get(pattern) {
+            Some(regex) => regex.clone(),
+            None => {
+                let regex = Regex::new(&format!("^(?:{pattern})$"))
+                    .map_err(|err| format!("invalid pattern {pattern}. {err}"))?;
+                patterns.entry(pattern).or_insert(regex).clone()
+            }
+        }
+    };
+
+    if regex.is_match(text) {
+        Ok(())
+    } else {
+        Err(format!("does not match {pattern}"))
+    }
+}
+
+/** Validator function, returns the failure message */
+pub fn custom<T: ?Sized>(value: &T, validator: fn(&T) -> Result<(), String>) -> Result<(), String> {
+    validator(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn len_counts_characters_and_items() {
+        assert!(len("héllo", 5, 5).is_ok());
+        assert!(len("", 1, 10).is_err());
+        assert!(len(&vec![1, 2, 3], 1, 2).is_err());
+        assert!(len(&None::<String>, 1, 2).is_ok());
+    }
+
+    #[test]
+    fn range_is_inclusive() {
+        assert!(range(&10u32, 1, 10).is_ok());
+        assert!(range(&11u32, 1, 10).is_err());
+        assert!(range(&-0.5f64, 0.0, 1.0).is_err());
+        assert!(range(&None::<i32>, 0, 1).is_ok());
+    }
+
+    #[test]
+    fn non_empty_rejects_empty_values() {
+        assert!(non_empty("").is_err());
+        assert!(non_empty(&vec![0]).is_ok());
+        assert!(non_empty(&HashMap::<u8, u8>::new()).is_err());
+    }
+
+    #[test]
+    fn regex_matches_whole_value() {
+        assert!(regex("abc-12", "[a-z]+-[0-9]+").is_ok());
+        assert!(regex("12-abc", "[a-z]+-[0-9]+").is_err());
+        assert!(regex("abc-12!", "[a-z]+-[0-9]+").is_err());
+        assert!(regex(&None::<String>, "[0-9]+").is_ok());
+        assert!(regex("a", "(").is_err());
+    }
+
+    #[test]
+    fn custom_returns_validator_message() {
+        fn even(value: &u32) -> Result<(), String> {
+            if value.is_multiple_of(2) { Ok(()) } else { Err("must be even".to_string()) }
+        }
+
+        assert_eq!(custom(&3, even), Err("must be even".to_string()));
+        assert!(custom(&4, even).is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use actix_web::{HttpResponse, guard::GuardContext, http::header::HeaderMap};
+
+use crate::{Error, common_gate::GateErrorResponse, error::ServiceError};
+
+/** Header with the service version a Context was compiled against */
+pub const VERSION_HEADER: &str = "rmt-version";
+
+/** Major of a semantic version, ```"v2.1.0"``` and ```"2.1.0"``` are 2 */
+pub fn major(version: &str) -> Option<u64> {
+    version.trim_start_matches('v')
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/** Breaking component of a semantic version.
+    Major for ```1.0.0``` and above, ```0.MINOR``` before it: ```"2.1.0"``` is ```(2, 0)```, ```"0.3.1"``` is ```(0, 3)```
+ */
+pub fn compatibility(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.trim_start_matches('v').split('.');
+
+    match parts.next()?.parse().ok()? {
+        0 => Some((0, parts.next().unwrap_or("0").parse().ok()?)),
+        major => Some((major, 0))
+    }
+}
+
+pub fn is_compatible(requested: &str, served: &str) -> bool {
+    compatibility(requested) == compatibility(served)
+}
+
+fn requested(headers: &HeaderMap) -> Option<&str> {
+    headers.get(VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+// Requests without version are served by the first mounted service of the route
+pub(crate) fn accepts(context: &GuardContext<'_>, served: &str) -> bool {
+    requested(context.head().headers())
+        .is_none_or(|requested| is_compatible(requested, served))
+}
+
+// Served when no mounted service of the route is compatible.
+// Served versions are returned in the version header
+pub(crate) async fn incompatible(request: actix_web::HttpRequest, served: Vec<&'static str>) -> HttpResponse {
+    let requested = requested(request.headers())
+        .unwrap_or_default()
+        .to_string();
+
+    HttpResponse::PreconditionFailed()
+        .insert_header((VERSION_HEADER, served.join(", ")))
+        .json(GateErrorResponse::from(Error::Service(ServiceError::IncompatibleVersion {
+            requested,
+            served: served.join(", ")
+        })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn major_is_compatibility_key() {
+        assert!(is_compatible("1.3.0", "v1.0.2"));
+        assert!(!is_compatible("1.3.0", "2.0.0"));
+        assert_eq!(compatibility("2.1.0"), Some((2, 0)));
+    }
+
+    #[test]
+    fn minor_is_compatibility_key_before_one() {
+        assert!(is_compatible("0.3.0", "0.3.7"));
+        assert!(!is_compatible("0.3.0", "0.4.0"));
+        assert_eq!(compatibility("v0"), Some((0, 0)));
+    }
+
+    #[test]
+    fn invalid_versions_are_rejected() {
+        assert_eq!(compatibility("x.1"), None);
+        assert_eq!(compatibility("0.x"), None);
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{Error, http::{Context, gate::Service}};
+use std::sync::{Arc, RwLock};
 
-#[allow(async_fn_in_trait)]
+use crate::{Error, http::{Context, RequestContext, Task, events::Subscription, gate::Service}};
 
 /** **Service Worker** 
  
@@ -9,15 +9,26 @@ use crate::{Error, http::{Context, gate::Service}};
     
     Use binding macros inside for simplicity
     ```
-    http_bind_worker!{ SERVICE_CONTEXT | MyService }
+    # use rmt::{http_bind_worker, http_context, http_gates, rmtm};
+    # http_gates!(MyService [ Ping { request: { }, response: { } } ]);
+    # static SERVICE_CONTEXT: rmt::http::Context<MyService> = http_context![ ::2020 ];
+    # #[derive(Clone)]
+    # pub struct MyWorker;
+    # #[rmtm::http_gate(MyService::Ping | MyWorker)]
+    # async fn process(self, _worker: &Self::W) -> Result<Self::Response, rmt::Error> { Ok(Self::Response { }) }
+    impl rmt::http::Worker for MyWorker {
+        http_bind_worker!{ SERVICE_CONTEXT | MyService }
+    }
+    # fn main() {}
     ```
 
     Is called by Service Instance.
  */
+#[allow(async_fn_in_trait)]
 pub trait Worker: Clone + Sync + Send {
     type S: Service;
 
-    async fn matcher(&self, request: <Self::S as Service>::Requests)
+    async fn matcher(&self, request: <Self::S as Service>::Requests, context: &RequestContext)
         -> Result<<Self::S as Service>::Responses, Error>;
 
     fn context_ref(&self) -> &'static Context<Self::S>;
@@ -35,4 +46,57 @@ pub trait Worker: Clone + Sync + Send {
     {
         Ok(response)
     }
+
+    /** Function is ran when the instance receives SIGHUP.
+        Swap settings with ```Reloadable::set``` only after they are loaded,
+        on error the old ones stay in place
+     */
+    async fn reload(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /** Background tasks, started with the instance and cancelled when it stops */
+    fn tasks(&self) -> Vec<Task<Self>> {
+        Vec::new()
+    }
+
+    /** Handlers of events published by other services, served on the events route */
+    fn subscriptions(&self) -> Vec<Subscription<Self>> {
+        Vec::new()
+    }
+}
+
+/** **Reloadable State**
+
+    Shared settings of a worker, swapped at once on reload.
+    Requests in flight keep the snapshot they got, subsequent ones see the new settings
+    ```ignore
+    let thresholds = worker.thresholds.get();
+    worker.thresholds.set(load_thresholds()?);
+    ```
+ */
+#[derive(Debug, Default)]
+pub struct Reloadable<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(value))))
+    }
+
+    /** Current snapshot */
+    pub fn get(&self) -> Arc<T> {
+        self.0.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(value);
+    }
 }
\ No newline at end of file
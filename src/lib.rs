@@ -3,6 +3,8 @@ pub mod origin;
 pub mod error;
 pub mod http;
 pub mod common_gate;
+pub mod metrics;
+pub mod config;
 
 pub use origin::Origin;
 pub use error::Error;
@@ -11,4 +13,5 @@ pub use common_gate::Payload;
 pub use rmtm;
 pub use paste;
 pub use serde;
+pub use reqwest;
 pub use log::{error, warn, debug, info, trace};
\ No newline at end of file
@@ -25,6 +25,22 @@ impl TryInto<log::Level> for LogLevel {
     }
 }
 
+impl std::str::FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(level: &str) -> Result<Self, ()> {
+        match level.to_ascii_lowercase().as_str() {
+            "silent" | "off" => Ok(LogLevel::Silent),
+            "error" => Ok(LogLevel::Error),
+            "warning" | "warn" => Ok(LogLevel::Warning),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(())
+        }
+    }
+}
+
 pub(crate) static mut LOG_LEVEL: LogLevel = LogLevel::Info;
 
 pub fn set_log_level(level: LogLevel) {
@@ -0,0 +1,82 @@
+use std::{collections::BTreeMap, fmt::Write, sync::{RwLock, atomic::{AtomicI64, Ordering}}};
+
+// Existing metrics are updated under the read lock
+static METRICS: RwLock<BTreeMap<String, AtomicI64>> = RwLock::new(BTreeMap::new());
+
+fn with_metric(name: &str, f: impl Fn(&AtomicI64)) {
+    let metrics = METRICS.read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some(metric) = metrics.get(name) {
+        return f(metric)
+    }
+    drop(metrics);
+
+    let mut metrics = METRICS.write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    f(metrics.entry(name.to_string()).or_default())
+}
+
+/** Adds to a counter */
+pub fn increment(name: &str, by: i64) {
+    with_metric(name, |metric| { metric.fetch_add(by, Ordering::Relaxed); });
+}
+
+/** Sets a gauge */
+pub fn set(name: &str, value: i64) {
+    with_metric(name, |metric| metric.store(value, Ordering::Relaxed));
+}
+
+pub fn get(name: &str) -> Option<i64> {
+    METRICS.read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(name)
+        .map(|metric| metric.load(Ordering::Relaxed))
+}
+
+/** All metrics, sorted by name */
+pub fn snapshot() -> BTreeMap<String, i64> {
+    METRICS.read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(name, metric)| (name.clone(), metric.load(Ordering::Relaxed)))
+        .collect()
+}
+
+/** All metrics in Prometheus text format, ```rmt.cache.MyService.hit``` is ```rmt_cache_MyService_hit``` */
+pub fn render() -> String {
+    snapshot().into_iter()
+        .fold(String::new(), |mut text, (name, value)| {
+            let name: String = name.chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' })
+                .collect();
+
+            let _ = writeln!(text, "{name} {value}");
+            text
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_and_gauges() {
+        increment("test.metrics.counter", 2);
+        increment("test.metrics.counter", 3);
+        set("test.metrics.gauge", 7);
+        set("test.metrics.gauge", 4);
+
+        assert_eq!(get("test.metrics.counter"), Some(5));
+        assert_eq!(get("test.metrics.gauge"), Some(4));
+        assert_eq!(get("test.metrics.missing"), None);
+    }
+
+    #[test]
+    fn render_sanitizes_names() {
+        set("test.render.My-Service.state", 1);
+
+        assert!(render().lines().any(|line| line == "test_render_My_Service_state 1"));
+    }
+}
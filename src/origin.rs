@@ -46,7 +46,7 @@ impl From<&Vec<Origin>> for AllowedOrigins {
     fn from(value: &Vec<Origin>) -> Self {
         let mut set = HashSet::new();
 
-        value.into_iter()
+        value.iter()
             .map(|origin| origin.host())
             .for_each(|ip| {
                 set.insert(ip.to_string());
@@ -0,0 +1,91 @@
+// Instance with two mounted services, started once per test binary
+#![allow(dead_code)]
+
+use std::{net::TcpStream, sync::Once, thread, time::Duration};
+
+use rmt::{Error, http::{Context, Instance, Worker}, http_bind_worker, http_build_info, http_context, http_gates, rmtm};
+
+pub const PORT: u16 = 42_180;
+pub const INTERNAL_PORT: u16 = 42_181;
+
+http_gates!(Users version "1.4.0" [
+    /// User by id
+    Get {
+        request: { id: u64 },
+        response: { name: String }
+    },
+    Purge {
+        request: { },
+        response: { },
+        access: Internal
+    }
+]);
+
+http_gates!(Orders version "2.1.0" [
+    List {
+        request: { #[serde(default)] limit: Option<u32> },
+        response: { ids: Vec<u64> }
+    }
+]);
+
+pub static USERS: Context<Users> = http_context![ ::PORT ].internal_port(INTERNAL_PORT);
+pub static ORDERS: Context<Orders> = http_context![ ::PORT ].mount("/orders");
+
+#[derive(Clone)]
+pub struct UsersWorker;
+
+#[derive(Clone)]
+pub struct OrdersWorker;
+
+impl Worker for UsersWorker {
+    http_bind_worker!{ USERS | Users }
+}
+
+impl Worker for OrdersWorker {
+    http_bind_worker!{ ORDERS | Orders }
+}
+
+#[rmtm::http_gate(Users::Get | UsersWorker)]
+async fn process(self, _worker: &Self::W) -> Result<Self::Response, Error> {
+    match self.id {
+        0 => Err(Error::Custom(Some("no user 0".to_string()))),
+        id => Ok(Self::Response { name: format!("user-{id}") })
+    }
+}
+
+#[rmtm::http_gate(Users::Purge | UsersWorker)]
+async fn process(self, _worker: &Self::W) -> Result<Self::Response, Error> {
+    Ok(Self::Response { })
+}
+
+#[rmtm::http_gate(Orders::List | OrdersWorker)]
+async fn process(self, _worker: &Self::W) -> Result<Self::Response, Error> {
+    Ok(Self::Response { ids: (1..=u64::from(self.limit.unwrap_or(3))).collect() })
+}
+
+/** Starts the instance in background and waits until its ports accept connections */
+pub fn start() {
+    static STARTED: Once = Once::new();
+
+    STARTED.call_once(|| {
+        thread::spawn(|| {
+            actix_web::rt::System::new().block_on(async {
+                Instance::new(UsersWorker)
+                    .mount(OrdersWorker)
+                    .set_describe(http_build_info!())
+                    .set_workers_count(1)
+                    .run()
+                    .await
+                    .expect("Test instance failed")
+            })
+        });
+
+        for port in [PORT, INTERNAL_PORT] {
+            let ready = (0..100).any(|_| {
+                TcpStream::connect(("127.0.0.1", port)).is_ok()
+                    || { thread::sleep(Duration::from_millis(50)); false }
+            });
+            assert!(ready, "Test instance is not listening on {port}");
+        }
+    });
+}
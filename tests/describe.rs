@@ -0,0 +1,70 @@
+use reqwest::{StatusCode, blocking};
+use serde_json::{Value, json};
+
+mod common;
+use common::{INTERNAL_PORT, PORT};
+
+fn describe(port: u16) -> blocking::Response {
+    blocking::get(format!("http://127.0.0.1:{port}/describe")).unwrap()
+}
+
+#[test]
+fn describe_lists_mounted_services_on_internal_port() {
+    common::start();
+
+    let description: Value = describe(INTERNAL_PORT).json().unwrap();
+    assert_eq!(description["build"]["package"], "rmt");
+    assert_eq!(description["build"]["rmt"], rmt::http::describe::RMT_VERSION);
+
+    assert_eq!(description["services"], json!([
+        {
+            "name": "Users",
+            "version": "1.4.0",
+            "mount": "",
+            "port": PORT,
+            "internal_port": INTERNAL_PORT,
+            "gates": [
+                {
+                    "name": "Get",
+                    "access": "Inherit",
+                    "doc": "User by id",
+                    "request": [{ "name": "id", "type": "u64", "required": true }],
+                    "response": [{ "name": "name", "type": "String", "required": true }],
+                    "internal": false
+                },
+                {
+                    "name": "Purge",
+                    "access": "Internal",
+                    "request": [],
+                    "response": [],
+                    "internal": true
+                }
+            ]
+        },
+        {
+            "name": "Orders",
+            "version": "2.1.0",
+            "mount": "/orders",
+            "port": PORT,
+            "gates": [
+                {
+                    "name": "List",
+                    "access": "Inherit",
+                    "request": [{ "name": "limit", "type": "Option<u32>", "required": false }],
+                    "response": [{ "name": "ids", "type": "Vec<u64>", "required": true }],
+                    "internal": false
+                }
+            ]
+        }
+    ]));
+}
+
+#[test]
+fn describe_is_hidden_on_public_port() {
+    common::start();
+
+    // Internal gates are not listed to public callers
+    let response = describe(PORT);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(!response.text().unwrap().contains("Purge"));
+}
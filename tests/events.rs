@@ -0,0 +1,140 @@
+// Events delivered to a subscriber which authenticates requests in its worker middleware
+use std::{net::TcpStream, sync::{Mutex, Once}, thread, time::Duration};
+
+use reqwest::{StatusCode, blocking};
+use serde_json::{Value, json};
+use rmt::{Error, error::ServiceError, http::{Context, Instance, Worker, events::{Publisher, Subscription}, version::VERSION_HEADER}, http_bind_worker, http_context, http_events, http_gates, rmtm};
+
+const PORT: u16 = 42_190;
+const INTERNAL_PORT: u16 = 42_191;
+const TOKEN: &str = "Bearer billing";
+
+http_events!(OrderEvents [
+    OrderCreated { id: u64 },
+    OrderCancelled { id: u64 }
+]);
+
+http_gates!(Billing version "1.0.0" [
+    Ping {
+        request: { },
+        response: { }
+    }
+]);
+
+static BILLING: Context<Billing> = http_context![ ::PORT ].internal_port(INTERNAL_PORT);
+
+// Ids of handled OrderCreated events
+static CREATED: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+#[derive(Clone)]
+pub struct BillingWorker;
+
+impl Worker for BillingWorker {
+    http_bind_worker!{ BILLING | Billing }
+
+    async fn middleware_pre(&self, request: actix_web::dev::ServiceRequest) -> Result<actix_web::dev::ServiceRequest, Error> {
+        match request.headers().get("authorization").is_some_and(|token| token == TOKEN) {
+            true => Ok(request),
+            false => Err(Error::Service(ServiceError::Unauthorized))
+        }
+    }
+
+    fn subscriptions(&self) -> Vec<Subscription<Self>> {
+        vec![Subscription::on(|_worker: Self, event: OrderCreated| async move {
+            CREATED.lock().unwrap().push(event.id);
+            Ok(())
+        })]
+    }
+}
+
+#[rmtm::http_gate(Billing::Ping | BillingWorker)]
+async fn process(self, _worker: &Self::W) -> Result<Self::Response, Error> {
+    Ok(Self::Response { })
+}
+
+fn start() {
+    static STARTED: Once = Once::new();
+
+    STARTED.call_once(|| {
+        thread::spawn(|| {
+            actix_web::rt::System::new().block_on(async {
+                Instance::new(BillingWorker)
+                    .set_workers_count(1)
+                    .run()
+                    .await
+                    .expect("Test instance failed")
+            })
+        });
+
+        let ready = (0..100).any(|_| {
+            TcpStream::connect(("127.0.0.1", INTERNAL_PORT)).is_ok()
+                || { thread::sleep(Duration::from_millis(50)); false }
+        });
+        assert!(ready, "Test instance is not listening on {INTERNAL_PORT}");
+    });
+}
+
+fn created() -> Vec<u64> {
+    CREATED.lock().unwrap().clone()
+}
+
+fn post(event: &str, payload: Value, token: Option<&str>) -> blocking::Response {
+    let request = blocking::Client::new()
+        .post(format!("http://127.0.0.1:{INTERNAL_PORT}/events"))
+        .json(&json!({ "id": format!("{event}-{payload}"), "source": "OrderEvents", "event": event, "payload": payload }));
+
+    match token {
+        Some(token) => request.header("authorization", token),
+        None => request
+    }.send().unwrap()
+}
+
+#[actix_web::test]
+async fn unauthenticated_publish_is_rejected() {
+    start();
+
+    let publisher = Publisher::<OrderEvents>::new(reqwest::Client::new())
+        .add_subscriber(&BILLING)
+        .set_retries(1, Duration::from_millis(1));
+
+    assert!(publisher.publish(OrderCreated { id: 1 }).await.is_err());
+    let response = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{INTERNAL_PORT}/events"))
+        .json(&json!({ "id": "unauthenticated", "source": "OrderEvents", "event": "OrderCreated", "payload": { "id": 1 } }))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(!response.status().is_success());
+    assert!(!created().contains(&1));
+}
+
+#[test]
+fn authenticated_events_reach_their_subscription() {
+    start();
+
+    assert_eq!(post("OrderCreated", json!({ "id": 2 }), Some(TOKEN)).status(), StatusCode::OK);
+    assert!(created().contains(&2));
+
+    // Unsubscribed events are acknowledged without handling
+    assert_eq!(post("OrderCancelled", json!({ "id": 3 }), Some(TOKEN)).status(), StatusCode::OK);
+    assert!(!created().contains(&3));
+
+    assert_eq!(post("OrderCreated", json!({ "id": "four" }), Some(TOKEN)).status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn events_of_incompatible_version_are_refused() {
+    start();
+
+    let response = blocking::Client::new()
+        .post(format!("http://127.0.0.1:{INTERNAL_PORT}/events"))
+        .header("authorization", TOKEN)
+        .header(VERSION_HEADER, "2.0.0")
+        .json(&json!({ "id": "incompatible", "source": "OrderEvents", "event": "OrderCreated", "payload": { "id": 5 } }))
+        .send()
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert!(!created().contains(&5));
+}
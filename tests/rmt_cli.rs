@@ -0,0 +1,97 @@
+use std::{io::Write, process::{Command, Output, Stdio}};
+
+use serde_json::{Value, json};
+
+mod common;
+use common::{INTERNAL_PORT, PORT};
+
+fn rmt_cli(args: &[&str], input: Option<&str>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmt-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Dropped stdin closes the input
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.unwrap_or_default().as_bytes()).unwrap();
+    drop(stdin);
+
+    child.wait_with_output().unwrap()
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn address() -> String {
+    format!("127.0.0.1:{PORT}")
+}
+
+#[test]
+fn call_returns_gate_response() {
+    common::start();
+
+    let output = rmt_cli(&[&address(), "call", "Get", r#"{"id": 7}"#], None);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output), json!({ "gate": "Get", "name": "user-7" }));
+
+    // Request is read from stdin
+    let output = rmt_cli(&[&address(), "call", "Get", "-"], Some(r#"{"id": 8}"#));
+    assert_eq!(json(&output), json!({ "gate": "Get", "name": "user-8" }));
+}
+
+#[test]
+fn call_resolves_mount_and_internal_port_from_describe() {
+    common::start();
+    let internal_port = INTERNAL_PORT.to_string();
+
+    let output = rmt_cli(&["--internal-port", &internal_port, &address(), "call", "List", r#"{"limit": 2}"#], None);
+    assert_eq!(json(&output), json!({ "gate": "List", "ids": [1, 2] }));
+
+    let output = rmt_cli(&["--internal-port", &internal_port, &address(), "call", "Purge"], Some(""));
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output), json!({ "gate": "Purge" }));
+
+    // Without the description the mount is unknown
+    let output = rmt_cli(&[&address(), "call", "List", "{}"], None);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn gate_errors_exit_with_1() {
+    common::start();
+
+    let output = rmt_cli(&[&address(), "call", "Get", r#"{"id": 0}"#], None);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no user 0"));
+
+    let output = rmt_cli(&[&address(), "call", "Get", "[1]"], None);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Request json must be an object"));
+}
+
+#[test]
+fn gates_lists_described_services() {
+    common::start();
+
+    let output = rmt_cli(&["--internal-port", &INTERNAL_PORT.to_string(), &address(), "gates"], None);
+    assert_eq!(output.status.code(), Some(0));
+
+    let listed = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = listed.lines().collect();
+    assert!(lines.contains(&"Users 1.4.0 "));
+    assert!(lines.contains(&"  Purge (internal)"));
+    assert!(lines.contains(&"    User by id"));
+    assert!(lines.contains(&"Orders 2.1.0 /orders"));
+    assert!(lines.contains(&"    request:  { limit?: Option<u32> }"));
+}
+
+#[test]
+fn invalid_arguments_exit_with_2() {
+    assert_eq!(rmt_cli(&["--timeout", "soon", "127.0.0.1:1", "gates"], None).status.code(), Some(2));
+    assert_eq!(rmt_cli(&["127.0.0.1:1", "send", "Get"], None).status.code(), Some(2));
+    assert_eq!(rmt_cli(&[], None).status.code(), Some(2));
+}
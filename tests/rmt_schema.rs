@@ -0,0 +1,83 @@
+use std::{path::PathBuf, process::{Command, Output}};
+
+use rmt::http::schema::ServiceSchema;
+
+mod common;
+use common::{INTERNAL_PORT, Orders, PORT, Users};
+
+fn schema_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rmt-schema-{}-{name}.json", std::process::id()))
+}
+
+fn written(name: &str, schema: &ServiceSchema) -> String {
+    let path = schema_file(name);
+    schema.write(&path).unwrap();
+    path.display().to_string()
+}
+
+fn rmt_schema(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rmt-schema"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn added_gate_is_not_breaking() {
+    let mut old = ServiceSchema::of::<Users>();
+    old.gates.retain(|gate| gate.name != "Purge");
+
+    let output = rmt_schema(&["diff", &written("added-old", &old), &written("added-new", &ServiceSchema::of::<Users>())]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output).trim(), "ok       AddedGate Purge");
+}
+
+#[test]
+fn removed_gate_and_field_are_breaking() {
+    let old = ServiceSchema::of::<Users>();
+    let mut new = old.clone();
+    new.gates.retain(|gate| gate.name != "Purge");
+    new.gates[0].response.clear();
+
+    let output = rmt_schema(&["diff", &written("removed-old", &old), &written("removed-new", &new)]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).lines().any(|line| line == "BREAKING RemovedGate Purge"));
+    assert!(stdout(&output).lines().any(|line| line.starts_with("BREAKING RemovedField Get")));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 breaking change(s) from Users 1.4.0 to 1.4.0"));
+}
+
+#[test]
+fn unreadable_schema_and_usage_exit_with_2() {
+    let schema = written("unreadable", &ServiceSchema::of::<Users>());
+
+    assert_eq!(rmt_schema(&["diff", &schema, "missing.json"]).status.code(), Some(2));
+    assert_eq!(rmt_schema(&["diff", &schema]).status.code(), Some(2));
+    assert_eq!(rmt_schema(&["export"]).status.code(), Some(2));
+}
+
+#[test]
+fn dump_writes_schema_of_running_service() {
+    common::start();
+    let address = format!("127.0.0.1:{INTERNAL_PORT}");
+    let path = schema_file("dump");
+
+    let output = rmt_schema(&["dump", &address, "Orders", &path.display().to_string()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(ServiceSchema::read(&path).unwrap(), ServiceSchema::of::<Orders>());
+
+    // Printed without the output file
+    let output = rmt_schema(&["dump", &address, "Users"]);
+    assert_eq!(stdout(&output).trim(), ServiceSchema::of::<Users>().to_json());
+
+    // Gates of the other service are removed ones
+    let output = rmt_schema(&["diff", &written("dump-users", &ServiceSchema::of::<Users>()), &path.display().to_string()]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Describe is not served on the public port
+    assert_eq!(rmt_schema(&["dump", &format!("127.0.0.1:{PORT}"), "Users"]).status.code(), Some(1));
+    assert_eq!(rmt_schema(&["dump", &address, "Missing"]).status.code(), Some(1));
+}